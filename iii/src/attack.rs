@@ -0,0 +1,124 @@
+use constants::Constants;
+use data::{Ship, State};
+use grid::{DIRS, Grid, Pos};
+use predict;
+use profile::Profile;
+
+/// Choose allies from `ships` to ram loaded enemy ships, as pairs of ally ID
+/// and the cell to move onto.
+///
+/// A collision spills both ships' cargo onto the cell, and whoever has more
+/// ships around is likely to collect it. Losing a ship is a wash against a
+/// single opponent, but in larger games the players left out come out
+/// ahead, so our ship counts against the attack in proportion to how many
+/// of them there are and how much of the game it has left to play.
+pub fn plan(constants: &Constants, profile: &Profile, state: &State, grid: &Grid, ships: &[&Ship]) -> Vec<(usize, Pos)> {
+
+    let params = &profile.params;
+    let players = state.scores.len();
+    let left = constants.MAX_TURNS.saturating_sub(state.round) as f64 / constants.MAX_TURNS as f64;
+    let ship_cost = constants.NEW_ENTITY_ENERGY_COST as f64 * left
+        * players.saturating_sub(2) as f64
+        / usize::max(players - 1, 1) as f64;
+
+    let structures = state.yards.iter()
+        .map(|yard| Pos(yard.x, yard.y))
+        .chain(state.drops.iter().map(|drop| Pos(drop.x, drop.y)))
+        .collect::<Vec<_>>();
+
+    let mut attacks = Vec::new();
+
+    for enemy in state.enemies().filter(|enemy| enemy.halite >= params.attack_min_cargo) {
+
+        // Aim for the enemy's most likely cell next turn
+        let moves = predict::moves(params, grid.rules(), state, enemy, grid.is_ship_inspired(enemy));
+        let (dir, chance) = DIRS.iter()
+            .zip(moves.iter())
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(dir, chance)| (*dir, *chance))
+            .expect("[INTERNAL ERROR]: no moves");
+
+        let target = grid.step(Pos(enemy.x, enemy.y), dir);
+        if structures.contains(&target) { continue }
+
+        // Neither colliding ship will be around to collect
+        let allies = grid.allies_around(target, params.attack_radius).saturating_sub(1) as f64;
+        let enemies = grid.enemies_around(target, params.attack_radius).saturating_sub(1) as f64;
+        let recover = if allies + enemies > 0.0 { allies / (allies + enemies) } else { 0.5 };
+
+        for ship in ships {
+            let pos = Pos(ship.x, ship.y);
+            if grid.dist(pos, target) != 1 || grid.move_cost(pos) > ship.halite { continue }
+
+            let spilled = (enemy.halite + ship.halite) as f64;
+            let gain = chance * (spilled * recover - ship.halite as f64 - ship_cost);
+            if gain >= params.attack_min_gain {
+                attacks.push((gain, ship.id, target));
+            }
+        }
+    }
+
+    attacks.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+
+    let mut chosen: Vec<(usize, Pos)> = Vec::new();
+    for (_, id, target) in attacks {
+        if chosen.iter().any(|(other, end)| *other == id || *end == target) { continue }
+        chosen.push((id, target));
+    }
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use grid::fixture;
+
+    /// Us at (2, 2) against a loaded enemy at (8, 8) heading east to its
+    /// yard, with our ship 0 next to the cell it's heading for.
+    fn state(enemy: usize, others: &[(usize, usize, usize)], yard: Pos) -> State {
+        let mut state = fixture::state(16, 10, [Pos(2, 2), yard]);
+        state.ships = vec![
+            Ship { owner: 0, id: 0, x: 9, y: 7, halite: 10 },
+            Ship { owner: 1, id: 1, x: 8, y: 8, halite: enemy },
+        ];
+        for (id, &(owner, x, y)) in others.iter().enumerate() {
+            state.ships.push(Ship { owner, id: id + 2, x, y, halite: 0 });
+        }
+        state
+    }
+
+    fn plan_for(state: &State) -> Vec<(usize, Pos)> {
+        let constants = Constants::default();
+        fixture::with_grid(&constants, state, |profile, grid| {
+            let ships = state.allies().collect::<Vec<_>>();
+            plan(&constants, profile, state, grid, &ships)
+        })
+    }
+
+    #[test]
+    fn rams_a_loaded_enemy_where_it_is_headed() {
+        let state = state(900, &[(0, 10, 9)], Pos(12, 8));
+        assert_eq!(plan_for(&state), vec![(0, Pos(9, 8))]);
+    }
+
+    #[test]
+    fn leaves_enemies_whose_allies_would_collect_the_spill() {
+        // Counting the rammer and its target more than once would make this
+        // look like a fair fight
+        let state = state(900, &[(1, 10, 10)], Pos(12, 8));
+        assert_eq!(plan_for(&state), vec![]);
+    }
+
+    #[test]
+    fn leaves_lightly_loaded_enemies() {
+        let state = state(200, &[(0, 10, 9)], Pos(12, 8));
+        assert_eq!(plan_for(&state), vec![]);
+    }
+
+    #[test]
+    fn never_rams_onto_a_structure() {
+        let state = state(900, &[(0, 10, 9)], Pos(9, 8));
+        assert_eq!(plan_for(&state), vec![]);
+    }
+}
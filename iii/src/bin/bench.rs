@@ -0,0 +1,290 @@
+extern crate failure;
+
+extern crate my_bot;
+
+use std::env;
+use std::io::{BufReader, BufWriter};
+use std::process::{Child, ChildStdin, Command as Process, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use my_bot::{Constants, Executor, Params, Player, Profile, Remote, Timed, PARAMS_VAR};
+
+const USAGE: &str = "usage: bench [--seeds S,S..S] [--sizes N,N] [--players P,P] [--turns T] [--jobs J] [--params FILE] BASELINE
+
+Plays this build's executor against the BASELINE bot command on every seed,
+map size and player count, once from each seat, and reports how it fared.
+This build plays with the parameters in FILE, or those in MY_BOT_PARAMS;
+the baseline never sees MY_BOT_PARAMS, so it plays with its own defaults.
+A baseline that times out like it would in the engine forfeits with a score of 0.";
+
+const INIT_TIMEOUT: u64 = 30;
+const TURN_TIMEOUT: u64 = 2;
+
+/// Two-sided 95% normal quantile
+const Z: f64 = 1.96;
+
+struct Options {
+    seeds: Vec<usize>,
+    sizes: Vec<usize>,
+    players: Vec<usize>,
+    turns: Option<usize>,
+    jobs: usize,
+    params: Params,
+    baseline: String,
+}
+
+/// A single game: which seat this build plays from, against baseline copies.
+#[derive(Copy, Clone)]
+struct Game {
+    seed: usize,
+    size: usize,
+    players: usize,
+    seat: usize,
+}
+
+/// Final scores of a game, split between this build and the baseline.
+struct Outcome {
+    game: Game,
+    ours: usize,
+    theirs: Vec<usize>,
+    /// Baseline seats that stopped answering
+    forfeits: usize,
+}
+
+fn list(value: &str) -> Result<Vec<usize>, failure::Error> {
+    let mut values = Vec::new();
+    for item in value.split(',') {
+        match item.find("..") {
+        | Some(split) => values.extend(item[..split].parse::<usize>()?..=item[split + 2..].parse()?),
+        | None => values.push(item.parse()?),
+        }
+    }
+    Ok(values)
+}
+
+fn options() -> Result<Options, failure::Error> {
+    let mut options = Options {
+        seeds: (1..=10).collect(),
+        sizes: vec![32, 48],
+        players: vec![2, 4],
+        turns: None,
+        jobs: thread::available_parallelism().map(usize::from).unwrap_or(1),
+        params: Params::default(),
+        baseline: String::new(),
+    };
+    let mut params = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| failure::err_msg(USAGE));
+        match arg.as_str() {
+        | "--seeds"   => options.seeds = list(&value()?)?,
+        | "--sizes"   => options.sizes = list(&value()?)?,
+        | "--players" => options.players = list(&value()?)?,
+        | "--turns"   => options.turns = Some(value()?.parse()?),
+        | "--jobs"    => options.jobs = usize::max(value()?.parse()?, 1),
+        | "--params"  => params = Some(value()?),
+        | _ if options.baseline.is_empty() => options.baseline = arg,
+        | _           => return Err(failure::err_msg(USAGE)),
+        }
+    }
+
+    options.params = Params::load(params.as_deref())?;
+
+    if options.baseline.is_empty() || options.players.iter().any(|players| *players < 2) {
+        return Err(failure::err_msg(USAGE))
+    }
+
+    Ok(options)
+}
+
+/// A baseline bot running as a child process.
+type Baseline = Remote<Timed<BufWriter<ChildStdin>>>;
+
+fn spawn(command: &str) -> Result<(Child, Baseline), failure::Error> {
+    let mut child = Process::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(env::temp_dir())
+        .env_remove(PARAMS_VAR)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let stdin = child.stdin.take().expect("[INTERNAL ERROR]: missing stdin");
+    let stdout = child.stdout.take().expect("[INTERNAL ERROR]: missing stdout");
+    let init = Duration::from_secs(INIT_TIMEOUT);
+    let turn = Duration::from_secs(TURN_TIMEOUT);
+    let remote = Remote::new(Timed::new(BufReader::new(stdout), BufWriter::new(stdin), init, turn));
+    Ok((child, remote))
+}
+
+fn run(options: &Options, game: Game) -> Result<Outcome, failure::Error> {
+    let mut constants = Constants {
+        GAME_SEED: game.seed,
+        ..Constants::default()
+    };
+    constants.MAX_TURNS = options.turns
+        .unwrap_or_else(|| constants.turns_for(game.size, game.size));
+
+    let (yards, halite) = my_bot::generate(&constants, game.players, game.size, game.size)?;
+    let state = my_bot::setup(&constants, game.size, game.size, yards, halite);
+
+    let total = state.halite.iter().sum();
+    let mut ours = Executor::new(total, Profile::new(game.players, game.size, game.size, options.params));
+    let mut children = Vec::new();
+    let mut remotes = Vec::new();
+    for _ in 1..game.players {
+        let (child, remote) = spawn(&options.baseline)?;
+        children.push(child);
+        remotes.push(remote);
+    }
+
+    let history = {
+        let mut players = remotes.iter_mut()
+            .map(|remote| remote as &mut dyn Player)
+            .collect::<Vec<_>>();
+        players.insert(game.seat, &mut ours);
+        my_bot::play(&constants, state, &mut players)
+    };
+
+    for mut child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    let mut theirs = history.last().scores.clone();
+    let ours = theirs.remove(game.seat);
+
+    let mut forfeits = 0;
+    for (score, remote) in theirs.iter_mut().zip(&remotes) {
+        if remote.stopped().is_some() {
+            *score = 0;
+            forfeits += 1;
+        }
+    }
+
+    Ok(Outcome { game, ours, theirs, forfeits })
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Unbiased sample variance.
+fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 { return 0.0 }
+    let mean = mean(values);
+    values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+/// 95% confidence interval for the mean of `values`.
+fn interval(values: &[f64]) -> (f64, f64) {
+    let spread = Z * (variance(values) / values.len() as f64).sqrt();
+    (mean(values) - spread, mean(values) + spread)
+}
+
+/// 95% Wilson score interval for a proportion of `wins` out of `n`.
+fn wilson(wins: usize, n: usize) -> (f64, f64) {
+    let (n, p) = (n as f64, wins as f64 / n as f64);
+    let center = (p + Z * Z / (2.0 * n)) / (1.0 + Z * Z / n);
+    let spread = Z / (1.0 + Z * Z / n) * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt();
+    (center - spread, center + spread)
+}
+
+fn report(players: usize, outcomes: &[&Outcome]) {
+    let n = outcomes.len();
+    let wins = outcomes.iter()
+        .filter(|outcome| outcome.theirs.iter().all(|theirs| outcome.ours > *theirs))
+        .count();
+    let ours = outcomes.iter()
+        .map(|outcome| outcome.ours as f64)
+        .collect::<Vec<_>>();
+    let theirs = outcomes.iter()
+        .flat_map(|outcome| outcome.theirs.iter().map(|theirs| *theirs as f64))
+        .collect::<Vec<_>>();
+
+    // Paired per game, which cancels out how rich each map is
+    let difference = outcomes.iter()
+        .map(|outcome| {
+            let theirs = outcome.theirs.iter().sum::<usize>() as f64 / outcome.theirs.len() as f64;
+            outcome.ours as f64 - theirs
+        })
+        .collect::<Vec<_>>();
+
+    let (low, high) = wilson(wins, n);
+    let forfeits = outcomes.iter()
+        .map(|outcome| outcome.forfeits)
+        .sum::<usize>();
+
+    let (below, above) = interval(&difference);
+    println!("{} players, {} games", players, n);
+    if forfeits > 0 {
+        println!("  forfeits    {} baseline seats timed out or exited", forfeits);
+    }
+    println!("  win rate    {:>5.1}% [{:.1}%, {:.1}%], even strength {:.1}%",
+        100.0 * wins as f64 / n as f64, 100.0 * low, 100.0 * high, 100.0 / players as f64);
+    println!("  ours        mean {:>9.1}  variance {:>13.1}", mean(&ours), variance(&ours));
+    println!("  baseline    mean {:>9.1}  variance {:>13.1}", mean(&theirs), variance(&theirs));
+    println!("  difference  mean {:>+9.1}  95% CI [{:+.1}, {:+.1}]", mean(&difference), below, above);
+    if below > 0.0 {
+        println!("  => better than baseline");
+    } else if above < 0.0 {
+        println!("  => worse than baseline");
+    } else {
+        println!("  => no significant difference");
+    }
+}
+
+fn main() -> Result<(), failure::Error> {
+
+    let options = options()?;
+    let mut games = Vec::new();
+    for &players in &options.players {
+        for &size in &options.sizes {
+            for &seed in &options.seeds {
+                for seat in 0..players {
+                    games.push(Game { seed, size, players, seat });
+                }
+            }
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs {
+            scope.spawn(|| {
+                while let Some(game) = games.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    match run(&options, *game) {
+                    | Ok(outcome) => {
+                        eprintln!(
+                            "seed {} size {} players {} seat {}: {} vs {:?}",
+                            game.seed, game.size, game.players, game.seat, outcome.ours, outcome.theirs,
+                        );
+                        outcomes.lock().expect("[INTERNAL ERROR]: poisoned lock").push(outcome);
+                    }
+                    | Err(error) => eprintln!("seed {} size {}: {}", game.seed, game.size, error),
+                    }
+                }
+            });
+        }
+    });
+
+    let outcomes = outcomes.into_inner().expect("[INTERNAL ERROR]: poisoned lock");
+    for &players in &options.players {
+        let group = outcomes.iter()
+            .filter(|outcome| outcome.game.players == players)
+            .collect::<Vec<_>>();
+        if !group.is_empty() {
+            report(players, &group);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+extern crate failure;
+
+extern crate my_bot;
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::{Child, Command as Process, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use my_bot::{Constants, Player, Recorder, Remote, State, Timed};
+
+const INIT_TIMEOUT: u64 = 30;
+const TURN_TIMEOUT: u64 = 2;
+
+const USAGE: &str = "usage: engine [--width W] [--height H] [--seed S] [--turns T] [--capture] [--replay FILE] BOT...
+
+A BOT of the form tcp:ADDRESS waits for a bot to connect to ADDRESS instead of spawning one.";
+
+struct Bot {
+    command: String,
+    child: Option<Child>,
+    remote: Remote<Timed<Box<dyn Write>>>,
+}
+
+impl Bot {
+    fn spawn(command: &str) -> Result<Self, failure::Error> {
+        if let Some(address) = command.strip_prefix("tcp:") {
+            eprintln!("[{}]: waiting for connection", command);
+            let (stream, _) = TcpListener::bind(address)?.accept()?;
+            stream.set_nodelay(true)?;
+            let reader = BufReader::new(stream.try_clone()?);
+            return Ok(Bot::new(command, None, reader, Box::new(stream)))
+        }
+
+        let mut child = Process::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("[INTERNAL ERROR]: missing stdin");
+        let stdout = child.stdout.take().expect("[INTERNAL ERROR]: missing stdout");
+        Ok(Bot::new(command, Some(child), BufReader::new(stdout), Box::new(stdin)))
+    }
+
+    fn new<R: BufRead + Send + 'static>(command: &str, child: Option<Child>, reader: R, writer: Box<dyn Write>) -> Self {
+        let init = Duration::from_secs(INIT_TIMEOUT);
+        let turn = Duration::from_secs(TURN_TIMEOUT);
+        Bot {
+            command: command.to_string(),
+            child,
+            remote: Remote::new(Timed::new(reader, writer, init, turn)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.remote.name().unwrap_or(&self.command)
+    }
+}
+
+struct Options {
+    width: Option<usize>,
+    height: Option<usize>,
+    seed: usize,
+    turns: Option<usize>,
+    capture: bool,
+    replay: Option<String>,
+    bots: Vec<String>,
+}
+
+fn options() -> Result<Options, failure::Error> {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_secs() as usize;
+
+    let mut options = Options {
+        width: None,
+        height: None,
+        seed,
+        turns: None,
+        capture: false,
+        replay: None,
+        bots: Vec::new(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| failure::err_msg(USAGE));
+        match arg.as_str() {
+        | "--width"   => options.width = Some(value()?.parse()?),
+        | "--height"  => options.height = Some(value()?.parse()?),
+        | "--seed"    => options.seed = value()?.parse()?,
+        | "--turns"   => options.turns = Some(value()?.parse()?),
+        | "--capture" => options.capture = true,
+        | "--replay"  => options.replay = Some(value()?),
+        | _           => options.bots.push(arg),
+        }
+    }
+
+    if options.bots.is_empty() {
+        return Err(failure::err_msg(USAGE))
+    }
+
+    Ok(options)
+}
+
+fn main() -> Result<(), failure::Error> {
+
+    let options = options()?;
+    let players = options.bots.len();
+    let mut constants = Constants {
+        GAME_SEED: options.seed,
+        CAPTURE_ENABLED: options.capture,
+        ..Constants::default()
+    };
+
+    let width = options.width.unwrap_or(constants.DEFAULT_MAP_WIDTH);
+    let height = options.height.unwrap_or(constants.DEFAULT_MAP_HEIGHT);
+    constants.MAX_TURNS = options.turns
+        .unwrap_or_else(|| constants.turns_for(width, height));
+
+    let (yards, halite) = my_bot::generate(&constants, players, width, height)?;
+    let state = my_bot::setup(&constants, width, height, yards, halite);
+
+    let mut bots = options.bots.iter()
+        .map(|command| Bot::spawn(command))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let history = {
+        let mut players = bots.iter_mut()
+            .map(|bot| &mut bot.remote as &mut dyn Player)
+            .collect::<Vec<_>>();
+        my_bot::play(&constants, state, &mut players)
+    };
+
+    for bot in &mut bots {
+        if let Some(round) = bot.remote.stopped() {
+            eprintln!("[{}]: stopped responding on turn {}", bot.name(), round);
+        }
+        if let Some(child) = &mut bot.child {
+            let _ = child.kill();
+        }
+    }
+
+    report(history.last(), &bots);
+
+    if let Some(path) = options.replay {
+        let names = bots.iter()
+            .map(|bot| bot.name().to_string())
+            .collect();
+
+        let mut recorder = Recorder::new(&constants, names, &history.states[0]);
+        for (turn, commands) in history.commands.iter().enumerate() {
+            recorder.record(&history.states[turn], commands, &history.states[turn + 1]);
+        }
+        recorder.save(path)?;
+    }
+
+    Ok(())
+}
+
+fn report(state: &State, bots: &[Bot]) {
+    let mut ranking = (0..bots.len()).collect::<Vec<_>>();
+    ranking.sort_by_key(|player| usize::MAX - state.scores[*player]);
+    for (rank, player) in ranking.into_iter().enumerate() {
+        println!(
+            "#{} player {} ({}) [{}]: {}",
+            rank + 1,
+            player,
+            bots[player].name(),
+            bots[player].command,
+            state.scores[player],
+        );
+    }
+}
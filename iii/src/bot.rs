@@ -0,0 +1,75 @@
+use std::iter;
+
+use failure;
+use serde_json;
+
+use constants::Constants;
+use data::State;
+use params::Params;
+use parse::ParseError;
+use profile::Profile;
+use strategy::Executor;
+use transport::Transport;
+
+/// Our bot, playing one game over `transport`.
+pub struct Bot<T> {
+    transport: T,
+    constants: Constants,
+    state: State,
+    executor: Executor,
+}
+
+impl<T: Transport> Bot<T> {
+    /// Read the constants and initial frame, and answer with our name.
+    pub fn new(mut transport: T, params: Params) -> Result<Self, failure::Error> {
+
+        let initial = transport.receive()
+            .ok_or(ParseError::MissingLine { line: 1, field: "constants" })?;
+        let constants = serde_json::from_str(&initial)?;
+        let state = State::initialize(&mut iter::from_fn(|| transport.receive()))?;
+
+        transport.send(&format!("nwtnni-{}", state.id))?;
+
+        let total = state.halite.iter()
+            .sum::<usize>();
+
+        let profile = Profile::new(state.scores.len(), state.width, state.height, params);
+        let executor = Executor::new(total, profile);
+
+        Ok(Bot { transport, constants, state, executor })
+    }
+
+    pub fn id(&self) -> usize {
+        self.state.id
+    }
+
+    /// Play turns until the engine stops sending frames.
+    pub fn play(mut self) -> Result<(), failure::Error> {
+        loop {
+            // Game over
+            let first = match self.transport.receive() {
+            | Some(ref line) if line.is_empty() => return Ok(()),
+            | Some(line) => line,
+            | None => return Ok(()),
+            };
+
+            let transport = &mut self.transport;
+            let mut lines = iter::once(first).chain(iter::from_fn(|| transport.receive()));
+
+            // Play on from the last good state rather than go silent
+            match self.state.update(&mut lines) {
+            | Ok(()) => (),
+            | Err(ParseError::MissingLine { .. }) => return Ok(()),
+            | Err(error) => error!("[{}]: malformed frame: {}", self.state.round + 1, error),
+            }
+
+            let commands = self.executor.execute(&self.constants, &self.state)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            self.transport.send(&commands)?;
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use grid::Dir;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -7,24 +9,25 @@ pub enum Command {
     Move(usize, Dir),
 }
 
-impl ToString for Command {
-    fn to_string(&self) -> String {
+impl fmt::Display for Command {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
-        | Command::Spawn         => "g".to_string(),
-        | Command::Transform(id) => format!("c {}", id),
-        | Command::Move(id, dir) => format!("m {} {}", id, dir.to_string()),
+        | Command::Spawn         => write!(fmt, "g"),
+        | Command::Transform(id) => write!(fmt, "c {}", id),
+        | Command::Move(id, dir) => write!(fmt, "m {} {}", id, dir),
         }
     }
 }
 
-impl ToString for Dir {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Dir {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let dir = match self {
         | Dir::N => "n",
         | Dir::S => "s",
         | Dir::E => "e",
         | Dir::W => "w",
         | Dir::O => "o",
-        }.to_string()
+        };
+        write!(fmt, "{}", dir)
     }
 }
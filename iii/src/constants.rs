@@ -1,8 +1,5 @@
 #![allow(dead_code, non_snake_case)]
 
-pub const RETURN: usize = 800;
-pub const HALITE_TIME_RATIO: usize = 100;
-
 fn capture_enabled() -> bool { false }
 fn capture_radius() -> usize { 3 }
 fn default_map_height() -> usize { 48 }
@@ -33,7 +30,7 @@ fn persistence() -> f32 { 0.7 }
 fn ships_above_for_capture() -> usize { 3 }
 fn strict_errors() -> bool { false }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Constants {
 
     #[serde(default = "capture_enabled")]
@@ -126,3 +123,51 @@ pub struct Constants {
     #[serde(default, rename = "game_seed")]
     pub GAME_SEED: usize,
 }
+
+impl Default for Constants {
+    fn default() -> Self {
+        Constants {
+            CAPTURE_ENABLED: capture_enabled(),
+            CAPTURE_RADIUS: capture_radius(),
+            DEFAULT_MAP_HEIGHT: default_map_height(),
+            DEFAULT_MAP_WIDTH: default_map_width(),
+            DROPOFF_COST: dropoff_cost(),
+            DROPOFF_PENALTY_RATIO: dropoff_penalty_ratio(),
+            EXTRACT_RATIO: extract_ratio(),
+            FACTOR_EXP_1: factor_exp_1(),
+            FACTOR_EXP_2: factor_exp_2(),
+            INITIAL_ENERGY: initial_energy(),
+            INSPIRATION_ENABLED: inspiration_enabled(),
+            INSPIRATION_RADIUS: inspiration_radius(),
+            INSPIRATION_SHIP_COUNT: inspiration_ship_count(),
+            INSPIRED_BONUS_MULTIPLIER: inspired_bonus_multiplier(),
+            INSPIRED_EXTRACT_RATIO: inspired_extract_ratio(),
+            INSPIRED_MOVE_COST_RATIO: inspired_move_cost_ratio(),
+            MAX_CELL_PRODUCTION: max_cell_production(),
+            MAX_ENERGY: max_energy(),
+            MAX_PLAYERS: max_players(),
+            MAX_TURNS: max_turns(),
+            MAX_TURN_THRESHOLD: max_turn_threshold(),
+            MIN_CELL_PRODUCTION: min_cell_production(),
+            MIN_TURNS: min_turns(),
+            MIN_TURN_THRESHOLD: min_turn_threshold(),
+            MOVE_COST_RATIO: move_cost_ratio(),
+            NEW_ENTITY_ENERGY_COST: new_entity_energy_cost(),
+            PERSISTENCE: persistence(),
+            SHIPS_ABOVE_FOR_CAPTURE: ships_above_for_capture(),
+            STRICT_ERRORS: strict_errors(),
+            GAME_SEED: 0,
+        }
+    }
+}
+
+impl Constants {
+    /// Turn limit the engine uses for a map of the given size.
+    pub fn turns_for(&self, width: usize, height: usize) -> usize {
+        let size = usize::max(width, height);
+        let lo = self.MIN_TURN_THRESHOLD;
+        let hi = usize::max(self.MAX_TURN_THRESHOLD, lo + 1);
+        let size = usize::min(usize::max(size, lo), hi);
+        self.MIN_TURNS + (size - lo) * (self.MAX_TURNS.saturating_sub(self.MIN_TURNS)) / (hi - lo)
+    }
+}
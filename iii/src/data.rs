@@ -9,6 +9,8 @@ pub struct State {
     pub ships: Vec<Ship>,
     pub yards: Vec<Shipyard>, 
     pub halite: Vec<usize>,
+    /// ID the next spawned ship gets; IDs are never reused
+    pub next_ship: usize,
 }
 
 impl State {
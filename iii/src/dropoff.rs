@@ -0,0 +1,113 @@
+use constants::Constants;
+use data::State;
+use grid::{Grid, Pos};
+use profile::Profile;
+
+/// Choose a site for our next dropoff, if the fleet is large enough and
+/// there's enough game left to justify one.
+pub fn plan(constants: &Constants, profile: &Profile, state: &State, grid: &Grid) -> Option<Pos> {
+
+    let params = &profile.params;
+    let ships = state.allies().count();
+    let drops = state.drops.iter()
+        .filter(|drop| drop.owner == state.id)
+        .count();
+
+    if ships < profile.ships_per_drop * (drops + 1)
+    || state.round + profile.params.drop_last_rounds >= constants.MAX_TURNS {
+        return None
+    }
+
+    let enemies = enemy_structures(state);
+    let max_distance = usize::max(params.drop_min_distance, usize::min(state.width, state.height) / 2);
+    let mut best = None;
+
+    for y in 0..state.height {
+        for x in 0..state.width {
+            let pos = Pos(x, y);
+            let distance = grid.distance_from_deposit(pos);
+
+            if distance < params.drop_min_distance
+            || distance > max_distance
+            || contested(profile, grid, &enemies, pos) {
+                continue
+            }
+
+            let density = grid.halite_around(pos, params.drop_radius);
+            if !dense(constants, profile, density) { continue }
+
+            let score = density.saturating_sub((distance - params.drop_min_distance) * params.drop_distance_penalty);
+            if best.is_none_or(|(best, _)| score > best) {
+                best = Some((score, pos));
+            }
+        }
+    }
+
+    best.map(|(_, pos)| pos)
+}
+
+/// Whether a planned dropoff at `site` is still worth building: there's
+/// enough game left, no enemy has built next to it since, the halite around
+/// it hasn't been mined out, and we can pay for it with `cargo` aboard the
+/// ship building it.
+pub fn viable(constants: &Constants, profile: &Profile, state: &State, grid: &Grid, site: Pos, cargo: usize) -> bool {
+    let cell = state.halite[site.1 * state.width + site.0];
+    state.round + profile.params.drop_last_rounds < constants.MAX_TURNS
+    && !contested(profile, grid, &enemy_structures(state), site)
+    && dense(constants, profile, grid.halite_around(site, profile.params.drop_radius))
+    && state.halite() >= grid.rules().dropoff_cost(cargo, cell)
+}
+
+fn enemy_structures(state: &State) -> Vec<Pos> {
+    state.yards.iter()
+        .map(|yard| (yard.owner, Pos(yard.x, yard.y)))
+        .chain(state.drops.iter().map(|drop| (drop.owner, Pos(drop.x, drop.y))))
+        .filter(|(owner, _)| *owner != state.id)
+        .map(|(_, pos)| pos)
+        .collect()
+}
+
+fn contested(profile: &Profile, grid: &Grid, enemies: &[Pos], pos: Pos) -> bool {
+    enemies.iter().any(|enemy| grid.dist(pos, *enemy) < profile.params.drop_enemy_distance)
+}
+
+fn dense(constants: &Constants, profile: &Profile, density: usize) -> bool {
+    density >= constants.DROPOFF_COST * profile.params.drop_density_ratio
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use data::{Dropoff, Ship};
+    use grid::fixture;
+
+    /// Our yard at (8, 16) and theirs at (24, 16), with `halite` on every
+    /// cell and one empty ship of ours.
+    fn state(halite: usize) -> State {
+        let mut state = fixture::state(32, halite, [Pos(8, 16), Pos(24, 16)]);
+        state.ships = vec![Ship { owner: 0, id: 0, x: 16, y: 8, halite: 0 }];
+        state
+    }
+
+    fn viable_at(state: &State, site: Pos) -> bool {
+        let constants = Constants::default();
+        fixture::with_grid(&constants, state, |profile, grid| {
+            viable(&constants, profile, state, grid, site, 0)
+        })
+    }
+
+    #[test]
+    fn density_counts_each_cell_once() {
+        // 61 cells within 5 steps: 61 * 200 clears 3 * DROPOFF_COST, 61 * 150 doesn't
+        assert!(viable_at(&state(200), Pos(16, 8)));
+        assert!(!viable_at(&state(150), Pos(16, 8)));
+    }
+
+    #[test]
+    fn sites_next_to_new_enemy_structures_are_abandoned() {
+        let mut state = state(200);
+        state.drops.push(Dropoff { owner: 1, x: 17, y: 8 });
+        assert!(!viable_at(&state, Pos(16, 8)));
+    }
+}
@@ -1,15 +1,19 @@
 use std::cmp;
 use std::iter;
 use std::mem;
-use std::usize;
 use std::collections::BinaryHeap;
+use std::time::Instant;
 
 use fixedbitset::FixedBitSet;
 use fnv::{FnvHashSet, FnvHashMap};
 
-use constants::HALITE_TIME_RATIO;
+use constants::Constants;
 use command::Command;
-use data::{Dropoff, Ship, Shipyard};
+use data::{Ship, State};
+use predict;
+use simulate;
+use profile::Profile;
+use rules::Rules;
 
 pub const DIRS: [Dir; 5] = [Dir::N, Dir::S, Dir::E, Dir::W, Dir::O];
 
@@ -33,69 +37,132 @@ impl Dir {
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Pos(pub usize, pub usize);
 
+/// First step of a planned route, before conflicts are resolved.
+#[derive(Copy, Clone, Debug)]
+struct Route {
+    id: usize,
+    value: usize,
+    start: Pos,
+    dir: Dir,
+    next: Pos,
+    crash: bool,
+}
+
 #[derive(Debug)]
 pub struct Grid<'round> {
+    constants: &'round Constants,
+    profile: &'round Profile,
+    rules: Rules,
     width: usize,
     height: usize,
-    round: usize,
     halite: &'round [usize],
     allies: FixedBitSet,
     enemies: FixedBitSet,
     stuck: FixedBitSet,
+    inspired: FixedBitSet,
+    inspired_ships: FnvHashSet<usize>,
+    collisions: Vec<f64>,
+    capture: Vec<usize>,
+    id: usize,
     base: Pos,
     drops: FnvHashSet<Pos>,
-    planned: Vec<(usize, Dir, Pos, bool)>,
+    planned: Vec<Route>,
+    reserved: FnvHashSet<(Pos, usize)>,
+    deadline: Instant,
+    paths: FnvHashMap<usize, Vec<Pos>>,
 }
 
 impl<'round> Grid<'round> {
+    /// Paths are searched until `deadline`, after which ships fall back on
+    /// `paths` left over from the previous round.
     pub fn new(
-        id: usize,
-        width: usize,
-        height: usize,
-        round: usize,
-        halite: &'round [usize],
-        ships: &[Ship],
-        dropoffs: &[Dropoff],
-        yards: &[Shipyard],
+        constants: &'round Constants,
+        profile: &'round Profile,
+        state: &'round State,
+        deadline: Instant,
+        paths: FnvHashMap<usize, Vec<Pos>>,
     ) -> Self {
+        let (id, width, height) = (state.id, state.width, state.height);
+        let rules = Rules::new(constants);
+        let halite = &state.halite;
         let mut allies = FixedBitSet::with_capacity(width * height);
         let mut enemies = FixedBitSet::with_capacity(width * height);
         let mut stuck = FixedBitSet::with_capacity(width * height);
+        let mut inspired = FixedBitSet::with_capacity(width * height);
+        let mut nearby = vec![0; width * height];
         let mut drops = FnvHashSet::default();
 
-        for ship in ships {
+        // Ships of each player within capture range of each cell
+        let mut capture = if constants.CAPTURE_ENABLED {
+            vec![0; state.scores.len() * width * height]
+        } else {
+            Vec::new()
+        };
+
+        for ship in &state.ships {
             if ship.owner == id {
-                let ship_index = ship.y * width + ship.x;
-                if ship.halite < halite[ship_index] / 10 {
-                    stuck.put(ship_index);
-                }
-                allies.put(ship_index);
+                allies.put(ship.y * width + ship.x);
             } else {
                 enemies.put(ship.y * width + ship.x);
+                stamp(&mut nearby, width, height, ship, constants.INSPIRATION_RADIUS);
+            }
+
+            if constants.CAPTURE_ENABLED {
+                let offset = ship.owner * width * height;
+                let counts = &mut capture[offset..offset + width * height];
+                stamp(counts, width, height, ship, constants.CAPTURE_RADIUS);
+            }
+        }
+
+        if constants.INSPIRATION_ENABLED {
+            for (index, count) in nearby.into_iter().enumerate() {
+                if count >= constants.INSPIRATION_SHIP_COUNT {
+                    inspired.put(index);
+                }
             }
         }
 
-        for drop in dropoffs {
+        for ship in state.allies() {
+            let ship_index = ship.y * width + ship.x;
+            if ship.halite < rules.move_cost(halite[ship_index], inspired[ship_index]) {
+                stuck.put(ship_index);
+            }
+        }
+
+        for drop in &state.drops {
             if drop.owner == id {
                 drops.insert(Pos(drop.x, drop.y));
             }
         }
 
-        let yard = yards[id];
+        let inspired_ships = simulate::inspiration(constants, state);
+        let collisions = predict::collisions(&profile.params, &rules, state, &inspired_ships);
+        let yard = state.yards[id];
         let base = Pos(yard.x, yard.y);
         let planned = Vec::new();
+        let reserved = FnvHashSet::default();
 
         Grid {
+            constants,
+            profile,
+            rules,
             width,
             height,
-            round,
             halite,
             allies,
             enemies,
             stuck,
+            inspired,
+            inspired_ships,
+            collisions,
+            capture,
+            id,
             base,
             drops,
             planned,
+            reserved,
+            deadline,
+            paths,
         }
     }
 
@@ -104,58 +171,90 @@ impl<'round> Grid<'round> {
         self.width * pos.1 + pos.0
     }
 
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Halite a ship of ours pays to move off `pos`.
+    pub fn move_cost(&self, pos: Pos) -> usize {
+        self.rules.move_cost(self.halite[self.index(pos)], self.is_inspired(pos))
+    }
+
+    /// Whether `ship`, of any player, is inspired this turn.
+    pub fn is_ship_inspired(&self, ship: &Ship) -> bool {
+        self.inspired_ships.contains(&ship.id)
+    }
+
     pub fn is_stuck(&self, pos: Pos) -> bool {
         self.stuck.contains(self.index(pos))
     }
 
-    pub fn distance_from_yard(&self, ship: &Ship) -> usize {
-        self.dist(Pos(ship.x, ship.y), self.base)
+    /// Whether a ship of ours mining at `pos` would be inspired.
+    pub fn is_inspired(&self, pos: Pos) -> bool {
+        self.inspired.contains(self.index(pos))
     }
 
-    pub fn dx(&self, x1: usize, x2: usize) -> usize {
-        let min_x = usize::min(x1, x2);
-        let max_x = usize::max(x1, x2);
-        if (max_x - min_x) > (self.width / 2) {
-            min_x + self.width - max_x
-        } else {
-            max_x - min_x
-        }
+    /// Chance that an enemy ship moves onto `pos` this turn.
+    pub fn collision_chance(&self, pos: Pos) -> f64 {
+        self.collisions[self.index(pos)]
     }
 
-    pub fn dy(&self, y1: usize, y2: usize) -> usize {
-        let min_y = usize::min(y1, y2);
-        let max_y = usize::max(y1, y2);
-        if (max_y - min_y) > (self.height / 2) {
-            min_y + self.height - max_y
-        } else {
-            max_y - min_y
-        }
+    fn captors(&self, player: usize, pos: Pos) -> usize {
+        self.capture[player * self.width * self.height + self.index(pos)]
+    }
+
+    /// Whether an ally at `pos` would be outnumbered enough to be captured.
+    pub fn is_capture_threat(&self, pos: Pos) -> bool {
+        if self.capture.is_empty() { return false }
+        let players = self.capture.len() / (self.width * self.height);
+        let allies = usize::max(self.captors(self.id, pos), 1);
+        (0..players)
+            .filter(|player| *player != self.id)
+            .any(|player| self.captors(player, pos) >= allies + self.constants.SHIPS_ABOVE_FOR_CAPTURE)
+    }
+
+    /// Enemy ships that one more ally within range would let us capture.
+    pub fn capture_targets<'a>(&'a self, state: &'a State) -> impl Iterator<Item = Pos> + 'a {
+        state.enemies()
+            .filter(move |_| !self.capture.is_empty())
+            .filter(move |ship| {
+                let pos = Pos(ship.x, ship.y);
+                self.captors(self.id, pos) + 1
+                    == self.captors(ship.owner, pos) + self.constants.SHIPS_ABOVE_FOR_CAPTURE
+            })
+            .map(|ship| Pos(ship.x, ship.y))
+    }
+
+    pub fn deposits(&self) -> impl Iterator<Item = Pos> + '_ {
+        iter::once(self.base).chain(self.drops.iter().cloned())
+    }
+
+    pub fn is_deposit(&self, pos: Pos) -> bool {
+        pos == self.base || self.drops.contains(&pos)
+    }
+
+    pub fn nearest_deposit(&self, pos: Pos) -> Pos {
+        self.deposits()
+            .min_by_key(|deposit| (self.dist(pos, *deposit), *deposit))
+            .unwrap_or(self.base)
+    }
+
+    pub fn distance_from_deposit(&self, pos: Pos) -> usize {
+        self.dist(pos, self.nearest_deposit(pos))
     }
 
     pub fn dist(&self, a: Pos, b: Pos) -> usize {
-        self.dx(a.0, b.0) + self.dy(a.1, b.1)
+        simulate::distance(self.width, self.height, (a.0, a.1), (b.0, b.1))
     }
 
     pub fn step(&self, p: Pos, d: Dir) -> Pos {
-        match d {
-        | Dir::N => Pos(p.0, (p.1 + self.height - 1) % self.height),
-        | Dir::S => Pos(p.0, (p.1 + 1) % self.height),
-        | Dir::E => Pos((p.0 + 1) % self.width, p.1),
-        | Dir::W => Pos((p.0 + self.width - 1) % self.width, p.1),
-        | Dir::O => p,
-        }
+        let (x, y) = simulate::shift(self.width, self.height, p.0, p.1, d);
+        Pos(x, y)
     }
 
-    fn around(&self, pos: Pos, radius: usize) -> impl Iterator<Item = Pos> {
-        let (w, h) = (self.width, self.height);
-        (0..radius).flat_map(move |y| {
-        (0..radius).flat_map(move |x| {
-            iter::once(Pos((pos.0 + w - x) % w, (pos.1 + h - y) % h))
-                .chain(iter::once(Pos((pos.0 + x) % w,     (pos.1 + h - y) % h)))
-                .chain(iter::once(Pos((pos.0 + w - x) % w, (pos.1 + y) % h)))
-                .chain(iter::once(Pos((pos.0 + x) % w,     (pos.1 + y) % h)))
-        })
-        })
+    /// Every cell within `radius` steps of `pos`, each exactly once.
+    pub fn around(&self, pos: Pos, radius: usize) -> impl Iterator<Item = Pos> {
+        around(self.width, self.height, pos, radius)
     }
 
     pub fn allies_around(&self, pos: Pos, radius: usize) -> usize {
@@ -170,18 +269,18 @@ impl<'round> Grid<'round> {
             .count()
     }
 
-//     pub fn halite_around(&self, pos: Pos, radius: usize) -> usize {
-//         self.around(pos, radius)
-//             .map(|pos| self.halite[self.index(pos)])
-//             .sum()
-//     }
+    pub fn halite_around(&self, pos: Pos, radius: usize) -> usize {
+        self.around(pos, radius)
+            .map(|pos| self.halite[self.index(pos)])
+            .sum()
+    }
 
 //     pub fn average_halite(&self) -> usize {
 //         self.halite.iter().sum::<usize>() / self.halite.len()
 //     }
 
-    pub fn fill_cost<F>(&self, costs: &mut Vec<usize>, f: F)
-        where F: Fn(&Self, Pos, usize) -> usize,
+    pub fn fill<T, F>(&self, values: &mut Vec<T>, f: F)
+        where F: Fn(&Self, Pos, usize) -> T,
     {
         for y in 0..self.height {
             let row = y * self.width;
@@ -189,15 +288,70 @@ impl<'round> Grid<'round> {
                 let index = row + x;
                 let pos = Pos(x, y);
                 let halite = self.halite[index];
-                costs.push(f(self, pos, halite));
+                values.push(f(self, pos, halite));
             }
         }
     }
 
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Paths planned this round, keyed by ship ID, for reuse next round.
+    pub fn take_paths(&mut self) -> FnvHashMap<usize, Vec<Pos>> {
+        mem::take(&mut self.paths)
+    }
+
     pub fn plan_route(&mut self, ship: &Ship, end: Pos, crash: bool) {
+        self.plan(ship, &[end], crash)
+    }
+
+    /// Send `ship` straight onto the adjacent cell `target`, collision or not.
+    pub fn plan_ram(&mut self, ship: &Ship, target: Pos) {
+        let start = Pos(ship.x, ship.y);
+        let dir = DIRS.iter()
+            .cloned()
+            .find(|dir| self.step(start, *dir) == target)
+            .unwrap_or(Dir::O);
+        self.paths.remove(&ship.id);
+        self.reserve(&[self.step(start, dir)], false, dir == Dir::O);
+        self.route(ship, dir, false);
+    }
+
+    /// Keep `ship` where it is, holding its cell against allies. A ship on a
+    /// deposit point steps off instead, so it doesn't shut out the ships
+    /// coming in to deposit.
+    pub fn plan_stay(&mut self, ship: &Ship) {
+        let start = Pos(ship.x, ship.y);
+        self.paths.remove(&ship.id);
+
+        if self.is_deposit(start) {
+            let next = self.step_toward(start, &[], false);
+            let dir = DIRS.iter()
+                .cloned()
+                .find(|dir| self.step(start, *dir) == next)
+                .unwrap_or(Dir::O);
+            self.route(ship, dir, false);
+            return
+        }
+
+        self.reserve(&[start], false, true);
+        self.route(ship, Dir::O, false);
+    }
+
+    /// Route `ship` toward whichever deposit point is cheapest to reach.
+    pub fn plan_return(&mut self, ship: &Ship, crash: bool) {
+        let deposits = self.deposits().collect::<Vec<_>>();
+        self.plan(ship, &deposits, crash)
+    }
+
+    fn plan(&mut self, ship: &Ship, ends: &[Pos], crash: bool) {
+
+        // Position and turn offset, with turns past the window folded together
+        type Key = (Pos, usize);
 
         #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-        struct Node(Pos, usize);
+        struct Node(Key, usize);
 
         impl PartialOrd for Node {
             fn partial_cmp(&self, rhs: &Self) -> Option<cmp::Ordering> {
@@ -212,126 +366,541 @@ impl<'round> Grid<'round> {
         }
 
         let start = Pos(ship.x, ship.y);
-        let start_index = self.index(start);
+        let window = self.profile.params.window;
 
-        if self.halite[start_index] / 10 > ship.halite || start == end {
-            self.planned.push((ship.id, Dir::O, start, crash));
+        if self.move_cost(start) > ship.halite || ends.contains(&start) {
+            self.reserve(&[start], crash, true);
+            self.route(ship, Dir::O, crash);
             return
         }
 
+        if self.is_expired() {
+            return self.plan_fast(ship, ends, crash)
+        }
+
         let mut queue = BinaryHeap::default();
-        let mut trace = FnvHashMap::default();
+        let mut trace: FnvHashMap<Key, (Key, Dir)> = FnvHashMap::default();
         let mut costs = FnvHashMap::default();
         let mut seen = FnvHashSet::default();
 
-        costs.insert(start, 0);
-        queue.push(Node(start, 0));
+        costs.insert((start, 0), 0);
+        queue.push(Node((start, 0), 0));
+
+        let mut popped = 0;
+
+        while let Some(Node(key, _)) = queue.pop() {
+
+            popped += 1;
+            if popped % 256 == 0 && self.is_expired() {
+                return self.plan_fast(ship, ends, crash)
+            }
 
-        while let Some(Node(node, _)) = queue.pop() {
+            let (node, turn) = key;
 
-            if node == end {
-                let mut step = end;
+            if ends.contains(&node) {
+                let mut path = vec![node];
+                let mut step = key;
                 let mut dir = Dir::O;
                 while let Some((prev, prev_dir)) = trace.get(&step) {
                     dir = *prev_dir;
-                    if *prev == start { break }
+                    if *prev == (start, 0) { break }
+                    path.push(prev.0);
                     step = *prev;
                 }
 
-                let next = self.step(start, dir);
-                self.planned.push((ship.id, dir, next, crash));
+                path.reverse();
+                self.reserve(&path, crash, false);
+                self.paths.insert(ship.id, path);
+
+                self.route(ship, dir, crash);
                 return
             }
 
-            seen.insert(node);
+            if !seen.insert(key) { continue }
 
             for dir in &DIRS {
 
+                // Waiting only helps while other ships' reservations apply
+                if *dir == Dir::O && turn >= window { continue }
+
                 let next = self.step(node, *dir);
                 let next_index = self.index(next);
+                let next_turn = usize::min(turn + 1, window);
+                let next_key = (next, next_turn);
+
+                // Enemies only move predictably for one turn
+                let risk = if turn == 0 && !self.is_deposit(next) {
+                    self.collision_chance(next)
+                } else {
+                    0.0
+                };
 
-                if seen.contains(&next) || self.stuck[next_index]
-                || (self.enemies_around(next, 1) > 0 && next != self.base) {
+                if seen.contains(&next_key) || self.stuck[next_index]
+                || risk >= self.profile.max_risk
+                || (self.is_capture_threat(next) && !ends.contains(&next))
+                || (turn < window && self.is_reserved(next, turn + 1, crash)) {
                     continue
                 }
 
-                let node_index = self.index(node);
                 let crowd_cost = if self.allies[next_index] {
                     // Don't even think about trying anything fancy
-                    if start == self.base { 1000000 } else { 1 }
+                    if self.is_deposit(start) { self.profile.params.yard_crowd_cost } else { 1 }
                 } else {
                     0
                 };
-                let halite_cost = (self.halite[node_index] / 10) / HALITE_TIME_RATIO;
+                let halite_cost = if *dir == Dir::O {
+                    0
+                } else {
+                    self.move_cost(node) / self.rules.halite_per_turn()
+                };
+                let risk_cost = (risk * (ship.halite + self.rules.spawn_cost()) as f64) as usize
+                    / self.rules.halite_per_turn();
                 let time_cost = 1;
-                let next_cost = costs[&node] + crowd_cost + halite_cost + time_cost;
+                let next_cost = costs[&key] + crowd_cost + halite_cost + risk_cost + time_cost;
 
-                if let Some(prev_cost) = costs.get(&next) {
+                if let Some(prev_cost) = costs.get(&next_key) {
                     if *prev_cost <= next_cost {
                         continue
                     }
                 }
 
-                let heuristic = self.dist(next, end);
-                trace.insert(next, (node, *dir));
-                costs.insert(next, next_cost);
-                queue.push(Node(next, next_cost + heuristic));
+                let heuristic = ends.iter()
+                    .map(|end| self.dist(next, *end))
+                    .min()
+                    .unwrap_or(0);
+                trace.insert(next_key, (key, *dir));
+                costs.insert(next_key, next_cost);
+                queue.push(Node(next_key, next_cost + heuristic));
             }
         }
 
-        warn!("[{}]: unable to path to {:?}", ship.id, end);
-        self.planned.push((ship.id, Dir::O, start, crash));
+        warn!("[{}]: unable to path to {:?}", ship.id, ends);
+        self.paths.remove(&ship.id);
+        self.reserve(&[start], crash, true);
+        self.route(ship, Dir::O, crash);
+    }
+
+    /// Cheap stand-in for `plan` once the deadline has passed: keep following
+    /// last round's path if it still leads to one of `ends`, and otherwise
+    /// take the single open step that gets closest.
+    ///
+    /// A ship that can't get closer steps aside rather than wait, since the
+    /// ally in its way may be waiting on it, and its step only claims the
+    /// next turn so it doesn't wall off the cell for later ships.
+    fn plan_fast(&mut self, ship: &Ship, ends: &[Pos], crash: bool) {
+        let start = Pos(ship.x, ship.y);
+
+        let cached = self.paths.remove(&ship.id)
+            .map(|path| match path.iter().position(|pos| *pos == start) {
+            | Some(index) => path[index + 1..].to_vec(),
+            | None => path,
+            })
+            .filter(|path| path.last().is_some_and(|end| ends.contains(end)))
+            .filter(|path| self.is_open(start, path[0], crash));
+
+        let path = match cached {
+        | Some(path) => {
+            self.reserve(&path, crash, false);
+            path
+        }
+        | None => vec![self.step_toward(start, ends, crash)],
+        };
+
+        let dir = DIRS.iter()
+            .cloned()
+            .find(|dir| self.step(start, *dir) == path[0])
+            .unwrap_or(Dir::O);
+
+        self.paths.insert(ship.id, path);
+        self.route(ship, dir, crash);
+    }
+
+    /// The open step from `start` that gets closest to `ends`, claimed for
+    /// the next turn only. Staying put counts as two steps farther.
+    fn step_toward(&mut self, start: Pos, ends: &[Pos], crash: bool) -> Pos {
+        let next = DIRS.iter()
+            .map(|dir| self.step(start, *dir))
+            .filter(|next| *next == start || self.is_open(start, *next, crash))
+            .min_by_key(|next| {
+                let distance = ends.iter().map(|end| self.dist(*next, *end)).min().unwrap_or(0);
+                if *next == start { distance + 2 } else { distance }
+            })
+            .unwrap_or(start);
+        self.reserved.insert((next, 1));
+        next
+    }
+
+    /// Whether a ship at `start` can safely step onto `next` this turn.
+    fn is_open(&self, start: Pos, next: Pos, crash: bool) -> bool {
+        self.dist(start, next) == 1
+            && !self.stuck[self.index(next)]
+            && (self.collision_chance(next) < self.profile.max_risk || self.is_deposit(next))
+            && !self.is_reserved(next, 1, crash)
+    }
+
+    fn route(&mut self, ship: &Ship, dir: Dir, crash: bool) {
+        let start = Pos(ship.x, ship.y);
+        self.planned.push(Route {
+            id: ship.id,
+            value: ship.halite,
+            start,
+            dir,
+            next: self.step(start, dir),
+            crash,
+        });
+    }
+
+    /// Whether another ally has claimed `pos` at `turn` turns from now.
+    /// Crashing ships ignore claims on deposit points.
+    fn is_reserved(&self, pos: Pos, turn: usize, crash: bool) -> bool {
+        !(crash && self.is_deposit(pos)) && self.reserved.contains(&(pos, turn))
+    }
+
+    /// Claim `path[t]` at `t + 1` turns from now, then hold its last cell
+    /// for the rest of the window unless it's a deposit point we'll leave.
+    /// A ship that `hold`s is staying where it is, even on a deposit point.
+    fn reserve(&mut self, path: &[Pos], crash: bool, hold: bool) {
+        let last = match path.last() {
+        | Some(last) => *last,
+        | None => return,
+        };
+
+        for turn in 1..=self.profile.params.window {
+            let pos = match path.get(turn - 1) {
+            | Some(pos) => *pos,
+            | None if !hold && self.is_deposit(last) => break,
+            | None => last,
+            };
+            if crash && self.is_deposit(pos) { break }
+            self.reserved.insert((pos, turn));
+        }
     }
 
+    /// Settle every planned move so that no two allies end on the same cell.
+    ///
+    /// Each ship may end either where it planned to go or where it started.
+    /// Ships are considered in decreasing order of value, and each tries to
+    /// claim its planned cell along an augmenting path: a ship already in
+    /// the way is pushed along its own planned move, which lets chains,
+    /// swaps and cycles of moves all go through. Ships that have already
+    /// claimed their planned cell are never displaced, and crashing ships
+    /// may share deposit points.
     pub fn resolve_routes(&mut self) -> (bool, Vec<Command>) {
 
-        let mut planned = mem::replace(&mut self.planned, Vec::with_capacity(0));
-        let routes = planned.len();
-        let mut resolved = Vec::with_capacity(routes);
-        let mut change;
-
-        loop {
-
-            change = None;
-
-            'outer: for i in 0..routes {
-                let (id_a, dir_a, next_a, crash_a) = planned[i];
-                for j in i + 1..routes {
-                    let (id_b, dir_b, next_b, crash_b) = planned[j];
-                    if next_a == next_b {
-                        if next_a == self.base && (crash_a || crash_b) {
-                            continue
-                        } else if dir_a == Dir::O {
-                            change = Some(id_b);
-                        } else if dir_b == Dir::O {
-                            change = Some(id_a);
-                        } else {
-                            change = Some(id_b);
-                        }
-                        break 'outer;
-                    }
-                }
-            }
+        let planned = mem::replace(&mut self.planned, Vec::with_capacity(0));
+        let mut owner = FnvHashMap::default();
+        let mut target = Vec::with_capacity(planned.len());
 
-            if let Some(id) = change {
-                for plan in &mut planned {
-                    if id == plan.0 {
-                        plan.2 = self.step(plan.2, plan.1.reflect());
-                        plan.1 = Dir::O;
-                        break
-                    }
-                }
-            } else {
-                break
+        // Staying put is always possible, since no two ships share a cell
+        for (i, route) in planned.iter().enumerate() {
+            owner.insert(route.start, i);
+            target.push(route.start);
+        }
+
+        let mut order = (0..planned.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| (usize::MAX - planned[*i].value, planned[*i].id));
+
+        for i in order {
+            let route = planned[i];
+            // Already pushed along its move by a higher-priority ship
+            if route.next == route.start || target[i] != route.start { continue }
+            owner.remove(&route.start);
+            let mut visited = FnvHashSet::default();
+            if !self.augment(&planned, i, true, &mut owner, &mut target, &mut visited) {
+                owner.insert(route.start, i);
+                target[i] = route.start;
             }
         }
 
         let mut spawnable = true;
-        for (id, dir, next, _) in planned {
-            if next == self.base { spawnable = false; }
-            resolved.push(Command::Move(id, dir));
+        let mut resolved = Vec::with_capacity(planned.len());
+        for (route, end) in planned.iter().zip(target) {
+            if end == self.base { spawnable = false; }
+            let dir = if end == route.next { route.dir } else { Dir::O };
+            resolved.push(Command::Move(route.id, dir));
         }
 
         (spawnable, resolved)
     }
+
+    fn augment(
+        &self,
+        planned: &[Route],
+        i: usize,
+        moving: bool,
+        owner: &mut FnvHashMap<Pos, usize>,
+        target: &mut [Pos],
+        visited: &mut FnvHashSet<Pos>,
+    ) -> bool {
+        let route = planned[i];
+        let claimed = target[i] == route.next && route.next != route.start;
+        let options = if moving || claimed {
+            &[route.next][..]
+        } else {
+            &[route.next, route.start][..]
+        };
+
+        for cell in options {
+            if route.crash && self.is_deposit(*cell) {
+                target[i] = *cell;
+                return true
+            }
+
+            if !visited.insert(*cell) { continue }
+
+            let free = match owner.get(cell).cloned() {
+            | None => true,
+            | Some(j) => j != i && self.augment(planned, j, false, owner, target, visited),
+            };
+
+            if free {
+                owner.insert(*cell, i);
+                target[i] = *cell;
+                return true
+            }
+        }
+
+        false
+    }
+}
+
+/// Every cell within `radius` steps of `pos` on a `width` by `height` map,
+/// each exactly once.
+fn around(width: usize, height: usize, pos: Pos, radius: usize) -> impl Iterator<Item = Pos> {
+    let (w, h) = (width as isize, height as isize);
+    let (x, y, r) = (pos.0 as isize, pos.1 as isize, radius as isize);
+
+    // Offsets past half the map would wrap onto cells already visited
+    (-isize::min(r, (h - 1) / 2)..=isize::min(r, h / 2)).flat_map(move |dy| {
+        let reach = r - dy.abs();
+        (-isize::min(reach, (w - 1) / 2)..=isize::min(reach, w / 2))
+            .map(move |dx| Pos((x + dx).rem_euclid(w) as usize, (y + dy).rem_euclid(h) as usize))
+    })
+}
+
+/// Increment `counts` at every cell within `radius` of `ship`.
+fn stamp(counts: &mut [usize], width: usize, height: usize, ship: &Ship, radius: usize) {
+    for pos in around(width, height, Pos(ship.x, ship.y), radius) {
+        counts[pos.1 * width + pos.0] += 1;
+    }
+}
+
+/// Boards and grids for unit tests elsewhere in the crate.
+#[cfg(test)]
+pub mod fixture {
+
+    use std::time::{Duration, Instant};
+
+    use fnv::FnvHashMap;
+
+    use super::*;
+    use data::Shipyard;
+    use params::Params;
+    use protocol;
+
+    /// Two players on a `size` by `size` map with `halite` on every cell and
+    /// their yards at `yards`, before any ships are built.
+    pub fn state(size: usize, halite: usize, yards: [Pos; 2]) -> State {
+        let yards = yards.iter()
+            .enumerate()
+            .map(|(owner, yard)| Shipyard { owner, x: yard.0, y: yard.1 })
+            .collect();
+        protocol::setup(&Constants::default(), size, size, yards, vec![halite; size * size])
+    }
+
+    /// Run `f` on a grid of `state` with the default profile for its size,
+    /// and no deadline any test comes close to.
+    pub fn with_grid<T, F>(constants: &Constants, state: &State, f: F) -> T
+        where F: FnOnce(&Profile, &mut Grid) -> T,
+    {
+        let profile = Profile::new(state.scores.len(), state.width, state.height, Params::default());
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut grid = Grid::new(constants, &profile, state, deadline, FnvHashMap::default());
+        f(&profile, &mut grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn capturing() -> Constants {
+        Constants { CAPTURE_ENABLED: true, ..Constants::default() }
+    }
+
+    /// An enemy at (8, 8) with `allies` of ours two steps away from it.
+    fn surrounded(allies: usize) -> State {
+        let mut state = fixture::state(16, 100, [Pos(2, 2), Pos(12, 12)]);
+        state.ships.push(Ship { owner: 1, id: 0, x: 8, y: 8, halite: 0 });
+        for (id, (x, y)) in [(6, 8), (10, 8), (8, 6), (8, 10)].iter().take(allies).enumerate() {
+            state.ships.push(Ship { owner: 0, id: id + 1, x: *x, y: *y, halite: 0 });
+        }
+        state
+    }
+
+    fn capture_targets(constants: &Constants, state: &State) -> Vec<Pos> {
+        fixture::with_grid(constants, state, |_, grid| grid.capture_targets(state).collect())
+    }
+
+    #[test]
+    fn capture_targets_are_one_ally_short() {
+        let constants = capturing();
+        assert_eq!(capture_targets(&constants, &surrounded(2)), vec![]);
+        assert_eq!(capture_targets(&constants, &surrounded(3)), vec![Pos(8, 8)]);
+        assert_eq!(capture_targets(&constants, &surrounded(4)), vec![]);
+        assert_eq!(capture_targets(&Constants::default(), &surrounded(3)), vec![]);
+    }
+
+    #[test]
+    fn capture_counts_each_ship_once_on_small_maps() {
+        // A radius of 3 reaches past every edge of a 4 by 4 map
+        let mut state = fixture::state(4, 100, [Pos(0, 0), Pos(2, 2)]);
+        state.ships.push(Ship { owner: 1, id: 0, x: 1, y: 1, halite: 0 });
+        for (id, (x, y)) in [(3, 1), (1, 3), (2, 0)].iter().enumerate() {
+            state.ships.push(Ship { owner: 0, id: id + 1, x: *x, y: *y, halite: 0 });
+        }
+        assert_eq!(capture_targets(&capturing(), &state), vec![Pos(1, 1)]);
+    }
+
+    #[test]
+    fn outnumbered_cells_are_capture_threats() {
+        let mut state = surrounded(0);
+        state.id = 1;
+        for (id, (x, y)) in [(6, 8), (10, 8), (8, 6), (8, 10)].iter().enumerate() {
+            state.ships.push(Ship { owner: 0, id: id + 1, x: *x, y: *y, halite: 0 });
+        }
+        fixture::with_grid(&capturing(), &state, |_, grid| {
+            assert!(grid.is_capture_threat(Pos(8, 8)));
+            assert!(!grid.is_capture_threat(Pos(2, 8)));
+        });
+    }
+
+    /// Our yard at (8, 8) on a map rich enough that every move costs time,
+    /// with full ships of ours at `ships`.
+    fn fleet(ships: &[(usize, usize)]) -> State {
+        let mut state = fixture::state(16, 1000, [Pos(8, 8), Pos(0, 0)]);
+        state.ships = ships.iter()
+            .enumerate()
+            .map(|(id, (x, y))| Ship { owner: 0, id, x: *x, y: *y, halite: 1000 })
+            .collect();
+        state
+    }
+
+    #[test]
+    fn later_ships_plan_around_reserved_slots() {
+        let state = fleet(&[(6, 5), (5, 5)]);
+        let path = fixture::with_grid(&Constants::default(), &state, |_, grid| {
+            grid.plan_stay(&state.ships[0]);
+            grid.plan_route(&state.ships[1], Pos(7, 5), false);
+            grid.take_paths().remove(&1)
+        });
+        let path = path.unwrap();
+        assert_eq!(path.last(), Some(&Pos(7, 5)));
+        assert!(!path.contains(&Pos(6, 5)), "path {:?}", path);
+    }
+
+    #[test]
+    fn ships_one_step_from_a_deposit_leave_it_free() {
+        // Ship 1 waits a turn behind ship 0 rather than going around
+        let state = fleet(&[(9, 8), (7, 8)]);
+        let paths = fixture::with_grid(&Constants::default(), &state, |_, grid| {
+            grid.plan_return(&state.ships[0], false);
+            grid.plan_return(&state.ships[1], false);
+            grid.take_paths()
+        });
+        assert_eq!(paths[&0], vec![Pos(8, 8)]);
+        assert_eq!(paths[&1], vec![Pos(7, 8), Pos(8, 8)]);
+    }
+
+    /// Resolve `moves[i]`, a direction and whether to crash, as planned by
+    /// ship `i` of `state`.
+    fn resolve(state: &State, moves: &[(Dir, bool)]) -> (bool, Vec<Command>) {
+        fixture::with_grid(&Constants::default(), state, |_, grid| {
+            for (ship, (dir, crash)) in state.ships.iter().zip(moves) {
+                grid.route(ship, *dir, *crash);
+            }
+            grid.resolve_routes()
+        })
+    }
+
+    fn commands(moves: &[Dir]) -> Vec<Command> {
+        moves.iter()
+            .enumerate()
+            .map(|(id, dir)| Command::Move(id, *dir))
+            .collect()
+    }
+
+    #[test]
+    fn swaps_go_through() {
+        let state = fleet(&[(5, 5), (6, 5)]);
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::W, false)]);
+        assert_eq!(moves, commands(&[Dir::E, Dir::W]));
+    }
+
+    #[test]
+    fn cycles_go_through() {
+        // Three ships can only go round a cycle by wrapping around the map
+        let mut state = fixture::state(3, 1000, [Pos(1, 1), Pos(2, 2)]);
+        state.ships = (0..3)
+            .map(|id| Ship { owner: 0, id, x: id, y: 0, halite: 1000 })
+            .collect();
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::E, false), (Dir::E, false)]);
+        assert_eq!(moves, commands(&[Dir::E, Dir::E, Dir::E]));
+
+        let state = fleet(&[(5, 5), (6, 5), (6, 6), (5, 6)]);
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::S, false), (Dir::W, false), (Dir::N, false)]);
+        assert_eq!(moves, commands(&[Dir::E, Dir::S, Dir::W, Dir::N]));
+    }
+
+    #[test]
+    fn chains_stop_behind_a_ship_that_stays() {
+        let state = fleet(&[(5, 5), (6, 5), (7, 5)]);
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::E, false), (Dir::O, false)]);
+        assert_eq!(moves, commands(&[Dir::O, Dir::O, Dir::O]));
+
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::E, false), (Dir::E, false)]);
+        assert_eq!(moves, commands(&[Dir::E, Dir::E, Dir::E]));
+    }
+
+    #[test]
+    fn the_most_loaded_ship_wins_a_contested_cell() {
+        let mut state = fleet(&[(5, 5), (7, 5)]);
+        state.ships[0].halite = 100;
+        let (_, moves) = resolve(&state, &[(Dir::E, false), (Dir::W, false)]);
+        assert_eq!(moves, commands(&[Dir::O, Dir::W]));
+    }
+
+    #[test]
+    fn crashing_ships_share_a_deposit() {
+        let state = fleet(&[(9, 8), (7, 8)]);
+        let (_, moves) = resolve(&state, &[(Dir::W, true), (Dir::E, true)]);
+        assert_eq!(moves, commands(&[Dir::W, Dir::E]));
+
+        let (_, moves) = resolve(&state, &[(Dir::W, false), (Dir::E, false)]);
+        assert_eq!(moves, commands(&[Dir::W, Dir::O]));
+    }
+
+    #[test]
+    fn ships_ending_on_the_yard_block_spawning() {
+        let state = fleet(&[(9, 8)]);
+        assert!(!resolve(&state, &[(Dir::W, false)]).0);
+        assert!(resolve(&state, &[(Dir::E, false)]).0);
+
+        // Leaving only counts if the move goes through
+        let state = fleet(&[(8, 8), (8, 7)]);
+        assert!(resolve(&state, &[(Dir::N, false), (Dir::N, false)]).0);
+        assert!(!resolve(&state, &[(Dir::N, false), (Dir::O, false)]).0);
+    }
+
+    #[test]
+    fn rams_drop_their_cached_path() {
+        let state = fleet(&[(5, 5)]);
+        let paths = fixture::with_grid(&Constants::default(), &state, |_, grid| {
+            grid.plan_route(&state.ships[0], Pos(5, 8), false);
+            grid.plan_ram(&state.ships[0], Pos(6, 5));
+            grid.take_paths()
+        });
+        assert!(paths.is_empty());
+    }
 }
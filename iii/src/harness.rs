@@ -0,0 +1,162 @@
+use serde_json;
+
+use command::Command;
+use constants::Constants;
+use data::State;
+use parse;
+use protocol;
+use simulate;
+use strategy::Executor;
+use transport::Transport;
+
+/// One seat in a game played in-process.
+pub trait Player {
+    /// Commands for this turn, given the game as seen by `state.id`.
+    fn play(&mut self, constants: &Constants, state: &State) -> Vec<Command>;
+
+    /// Whether this player can still issue commands; one that can't no
+    /// longer counts as active.
+    fn alive(&self) -> bool {
+        true
+    }
+}
+
+impl Player for Executor {
+    fn play(&mut self, constants: &Constants, state: &State) -> Vec<Command> {
+        self.execute(constants, state)
+    }
+}
+
+impl<F: FnMut(&Constants, &State) -> Vec<Command>> Player for F {
+    fn play(&mut self, constants: &Constants, state: &State) -> Vec<Command> {
+        self(constants, state)
+    }
+}
+
+/// A bot on the other end of a transport, such as another build of this one
+/// running as a separate process.
+///
+/// A bot that stops answering, for instance because its transport timed
+/// out, issues no more commands.
+pub struct Remote<T> {
+    transport: T,
+    name: Option<String>,
+    prev: Option<State>,
+    stopped: Option<usize>,
+}
+
+impl<T: Transport> Remote<T> {
+    pub fn new(transport: T) -> Self {
+        Remote { transport, name: None, prev: None, stopped: None }
+    }
+
+    /// Name the bot answered initialization with.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Round on which the bot stopped answering, if it has.
+    pub fn stopped(&self) -> Option<usize> {
+        self.stopped
+    }
+
+    fn send(&mut self, round: usize, lines: &[String]) {
+        for line in lines {
+            if self.stopped.is_none() && self.transport.send(line).is_err() {
+                self.stopped = Some(round);
+            }
+        }
+    }
+
+    fn receive(&mut self, round: usize) -> Option<String> {
+        if self.stopped.is_some() { return None }
+        let line = self.transport.receive();
+        if line.is_none() {
+            self.stopped = Some(round);
+        }
+        line
+    }
+}
+
+impl<T: Transport> Player for Remote<T> {
+    fn play(&mut self, constants: &Constants, state: &State) -> Vec<Command> {
+        let prev = match self.prev.take() {
+        | Some(prev) => prev,
+        | None => {
+            let mut frame = vec![serde_json::to_string(constants).unwrap_or_default()];
+            frame.extend(protocol::initialization(state, state.id));
+            self.send(state.round, &frame);
+            self.name = self.receive(state.round);
+            state.clone()
+        }
+        };
+
+        self.send(state.round, &protocol::update(&prev, state));
+        self.prev = Some(state.clone());
+        self.receive(state.round)
+            .map(|line| parse::commands(&line))
+            .unwrap_or_default()
+    }
+
+    fn alive(&self) -> bool {
+        self.stopped.is_none()
+    }
+}
+
+/// Everything that happened in a game.
+#[derive(Debug, Clone)]
+pub struct History {
+    /// `states[t]` is the state turn `t + 1` was played from; the last is the
+    /// final state
+    pub states: Vec<State>,
+    /// `commands[t][player]` holds what `player` issued on turn `t + 1`
+    pub commands: Vec<Vec<Vec<Command>>>,
+}
+
+impl History {
+    pub fn last(&self) -> &State {
+        self.states.last().expect("[INTERNAL ERROR]: empty history")
+    }
+}
+
+/// Play `players` against each other from `state` until the last turn, or
+/// until at most one of them can still do anything.
+///
+/// `players[i]` plays as player `i`. Nothing is timed or piped, so a game is
+/// exactly as deterministic as its players.
+pub fn play(constants: &Constants, mut state: State, players: &mut [&mut dyn Player]) -> History {
+
+    assert_eq!(players.len(), state.scores.len(), "[INTERNAL ERROR]: one player per seat");
+
+    state.round += 1;
+
+    let mut history = History {
+        states: vec![state.clone()],
+        commands: Vec::new(),
+    };
+
+    while state.round <= constants.MAX_TURNS {
+
+        let commands = players.iter_mut()
+            .enumerate()
+            .map(|(id, player)| player.play(constants, &State { id, ..state.clone() }))
+            .collect::<Vec<_>>();
+
+        let next = simulate::step(constants, &state, &commands);
+        history.states.push(next.clone());
+        history.commands.push(commands);
+        state = next;
+
+        let active = (0..players.len())
+            .filter(|player| players[*player].alive())
+            .filter(|player| {
+                state.ships.iter().any(|ship| ship.owner == *player)
+                || state.scores[*player] >= constants.NEW_ENTITY_ENERGY_COST
+            })
+            .count();
+
+        if players.len() > 1 && active <= 1 { break }
+    }
+
+    history
+}
@@ -1,19 +1,51 @@
+extern crate failure;
 extern crate fixedbitset;
 extern crate fnv;
 #[macro_use]
 extern crate log;
-extern crate hungarian;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate zstd;
 
+mod attack;
+mod bot;
 mod constants;
 mod command;
 mod data;
+mod dropoff;
 mod grid;
+mod harness;
+mod mapgen;
+mod matching;
+mod mining;
+mod params;
 mod parse;
+mod predict;
+mod profile;
+mod recall;
+mod protocol;
+mod replay;
+mod rules;
+mod simulate;
+mod spawn;
 mod strategy;
+mod transport;
 
-pub use data::State;
+pub use bot::Bot;
+pub use command::Command;
+pub use constants::Constants;
+pub use data::{Dropoff, Ship, Shipyard, State};
+pub use grid::Dir;
+pub use harness::{play, History, Player, Remote};
+pub use mapgen::generate;
+pub use params::{Params, PARAMS_VAR};
+pub use parse::{commands, ParseError};
+pub use profile::Profile;
+pub use protocol::{initialization, setup, update};
+pub use replay::{Recorder, Replay};
+pub use rules::Rules;
+pub use simulate::step;
 pub use strategy::Executor;
+pub use transport::{Channel, Stream, Timed, Transport};
@@ -1,32 +1,49 @@
-extern crate log; 
 extern crate failure;
+#[macro_use]
+extern crate log;
 extern crate simplelog;
-extern crate serde_json;
 
 extern crate my_bot;
 
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::env;
 use std::fs::File;
 
 use simplelog::*;
 
-use my_bot::{Executor, State};
+use my_bot::{Bot, Params, Stream, Transport};
 
-fn main() -> Result<(), failure::Error> {
+const USAGE: &str = "usage: my_bot [--tcp ADDRESS] [--params FILE]
 
-    let stdin = std::io::stdin();
-    let stdout = std::io::stdout();
+Tuning parameters are read as JSON from FILE, or else from MY_BOT_PARAMS.";
 
-    let mut reader = BufReader::new(stdin.lock())
-        .lines()
-        .filter_map(|line| line.ok())
-        .peekable();
+fn main() -> Result<(), failure::Error> {
+    let mut tcp = None;
+    let mut params = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| failure::err_msg(USAGE));
+        match arg.as_str() {
+        | "--tcp"    => tcp = Some(value()?),
+        | "--params" => params = Some(value()?),
+        | _          => return Err(failure::err_msg(USAGE)),
+        }
+    }
+
+    let params = Params::load(params.as_deref())?;
+    match tcp {
+    | Some(address) => run(Stream::connect(address)?, params),
+    | None          => run(Stream::stdio(), params),
+    }
+}
 
-    let initial = reader.next().expect("[INTERNAL ERROR]: missing constants");
-    let constants = serde_json::from_str(&initial)?;
-    let mut writer = BufWriter::new(stdout.lock());
-    let mut state = State::initialize(&mut reader);
-    let log = format!("halite-{}.log", state.id);
+fn run<T: Transport>(transport: T, params: Params) -> Result<(), failure::Error> {
+    // The log is named after our ID, which only the init frame tells us
+    let bot = Bot::new(transport, params);
+    let log = match &bot {
+    | Ok(bot) => format!("halite-{}.log", bot.id()),
+    | Err(_)  => String::from("halite-init.log"),
+    };
 
     WriteLogger::init(
         LevelFilter::Info,
@@ -34,27 +51,8 @@ fn main() -> Result<(), failure::Error> {
         File::create(log)?
     )?;
 
-    write!(writer, "nwtnni-{}\n", state.id)?;
-    writer.flush()?;
+    let bot = bot.inspect_err(|error| error!("failed to start: {}", error))?;
 
-    let total = state.halite.iter()
-        .sum::<usize>();
-
-    let mut executor = Executor::new(total);
-    
-    loop {
-        // Game over
-        if reader.peek().map_or(true, |line| line.is_empty()) {
-            return Ok(())
-        }
-
-        state.update(&mut reader);
-
-        for command in executor.execute(&constants, &state) {
-            write!(writer, "{} ", command.to_string())?;
-        }
-
-        write!(writer, "\n")?;
-        writer.flush()?;
-    }
+    info!("{:?}", params);
+    bot.play()
 }
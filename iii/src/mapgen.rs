@@ -0,0 +1,211 @@
+use failure;
+
+use constants::Constants;
+use data::Shipyard;
+
+/// 32-bit Mersenne Twister, the same generator as `std::mt19937`.
+struct Twister {
+    state: [u32; 624],
+    index: usize,
+}
+
+impl Twister {
+    fn new(seed: u32) -> Self {
+        let mut state = [0; 624];
+        state[0] = seed;
+        for i in 1..624 {
+            state[i] = 1812433253u32
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Twister { state, index: 624 }
+    }
+
+    fn next(&mut self) -> u32 {
+        if self.index >= 624 {
+            for i in 0..624 {
+                let y = (self.state[i] & 0x8000_0000) | (self.state[(i + 1) % 624] & 0x7fff_ffff);
+                let mut x = self.state[(i + 397) % 624] ^ (y >> 1);
+                if y & 1 != 0 { x ^= 0x9908_b0df; }
+                self.state[i] = x;
+            }
+            self.index = 0;
+        }
+
+        let mut y = self.state[self.index];
+        self.index += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^ (y >> 18)
+    }
+
+    /// Uniform sample from `[0, 1)`, drawn the way `std::uniform_real_distribution<double>` does.
+    fn uniform(&mut self) -> f64 {
+        let lo = f64::from(self.next());
+        let hi = f64::from(self.next());
+        let range = 4_294_967_296.0;
+        let sample = (lo + hi * range) / (range * range);
+        if sample >= 1.0 { 1.0 - f64::EPSILON } else { sample }
+    }
+}
+
+/// Generate a symmetric `width` by `height` map for `players` from
+/// `constants.GAME_SEED`.
+///
+/// Returns shipyards indexed by player and the row-major halite vector,
+/// in the same shape `State::initialize` produces. Only 1, 2 and 4 players
+/// can be placed symmetrically.
+pub fn generate(
+    constants: &Constants,
+    players: usize,
+    width: usize,
+    height: usize,
+) -> Result<(Vec<Shipyard>, Vec<usize>), failure::Error> {
+
+    // Players each get one tile; tiles are mirrored across the map's axes
+    let (cols, rows) = match players {
+    | 1 => (1, 1),
+    | 2 => (2, 1),
+    | 4 => (2, 2),
+    | _ => return Err(failure::err_msg(format!("unsupported player count: {}", players))),
+    };
+
+    let (tile_width, tile_height) = (width / cols, height / rows);
+    if tile_width == 0 || tile_height == 0 {
+        return Err(failure::err_msg(format!("map too small: {}x{}", width, height)))
+    }
+
+    let mut rng = Twister::new(constants.GAME_SEED as u32);
+
+    let tile = tile(constants, &mut rng, tile_width, tile_height);
+
+    let mut halite = vec![0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let (tx, ty) = (x % tile_width, y % tile_height);
+            let tx = if (x / tile_width) % 2 == 1 { tile_width - 1 - tx } else { tx };
+            let ty = if (y / tile_height) % 2 == 1 { tile_height - 1 - ty } else { ty };
+            halite[y * width + x] = tile[ty * tile_width + tx];
+        }
+    }
+
+    let yards = (0..players)
+        .map(|owner| {
+            let (col, row) = (owner % cols, owner / cols);
+            let x = tile_width / 2;
+            let y = tile_height / 2;
+            let x = col * tile_width + if col % 2 == 1 { tile_width - 1 - x } else { x };
+            let y = row * tile_height + if row % 2 == 1 { tile_height - 1 - y } else { y };
+            halite[y * width + x] = 0;
+            Shipyard { owner, x, y }
+        })
+        .collect();
+
+    Ok((yards, halite))
+}
+
+/// Fractal value noise over a single `width` by `height` tile, scaled into
+/// `[0, production]` for a production level drawn between
+/// `MIN_CELL_PRODUCTION` and `MAX_CELL_PRODUCTION`.
+fn tile(constants: &Constants, rng: &mut Twister, width: usize, height: usize) -> Vec<usize> {
+
+    let source = (0..width * height)
+        .map(|_| rng.uniform().powf(f64::from(constants.FACTOR_EXP_1)))
+        .collect::<Vec<_>>();
+
+    let octaves = (usize::max(width, height) as f64).log2().floor() as usize + 1;
+    let persistence = f64::from(constants.PERSISTENCE);
+    let mut amplitude = 1.0;
+    let mut region = vec![0.0; width * height];
+
+    for octave in 2..=octaves {
+        let wavelength = 1 << (octaves - octave);
+        amplitude *= persistence;
+        for (cell, noise) in region.iter_mut().zip(smooth(&source, width, height, wavelength)) {
+            *cell += amplitude * noise;
+        }
+    }
+
+    let max = region.iter().cloned().fold(0.0, f64::max);
+    if max > 0.0 {
+        for cell in &mut region {
+            *cell = (*cell / max).powf(f64::from(constants.FACTOR_EXP_2));
+        }
+    }
+
+    let spread = constants.MAX_CELL_PRODUCTION.saturating_sub(constants.MIN_CELL_PRODUCTION) + 1;
+    let production = constants.MIN_CELL_PRODUCTION + rng.next() as usize % spread;
+
+    region.into_iter()
+        .map(|cell| (cell * production as f64).round() as usize)
+        .collect()
+}
+
+/// Sample `source` every `wavelength` cells and interpolate between samples,
+/// wrapping around the tile edges.
+fn smooth(source: &[f64], width: usize, height: usize, wavelength: usize) -> Vec<f64> {
+    let cols = width.div_ceil(wavelength);
+    let rows = height.div_ceil(wavelength);
+    let sample = |col: usize, row: usize| {
+        let x = (col % cols) * wavelength;
+        let y = (row % rows) * wavelength;
+        source[y * width + x]
+    };
+
+    let mut noise = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (row, fy) = (y / wavelength, (y % wavelength) as f64 / wavelength as f64);
+        for x in 0..width {
+            let (col, fx) = (x / wavelength, (x % wavelength) as f64 / wavelength as f64);
+            let top = lerp(sample(col, row), sample(col + 1, row), fx);
+            let bottom = lerp(sample(col, row + 1), sample(col + 1, row + 1), fx);
+            noise.push(lerp(top, bottom, fy));
+        }
+    }
+    noise
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a * (1.0 - t) + b * t
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn maps_are_mirrored_between_players() {
+        let constants = Constants { GAME_SEED: 11, ..Constants::default() };
+        let (width, height) = (40, 32);
+        for players in [2, 4] {
+            let (yards, halite) = generate(&constants, players, width, height).unwrap();
+            let at = |x: usize, y: usize| halite[y * width + x];
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(at(x, y), at(width - 1 - x, y));
+                    if players == 4 {
+                        assert_eq!(at(x, y), at(x, height - 1 - y));
+                    }
+                }
+            }
+            let yard = |x: usize, y: usize| yards.iter().any(|yard| (yard.x, yard.y) == (x, y));
+            for other in &yards {
+                assert!(yard(width - 1 - other.x, other.y));
+                if players == 4 {
+                    assert!(yard(other.x, height - 1 - other.y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_player_counts_are_rejected() {
+        let constants = Constants::default();
+        for players in [0, 3, 5, 8] {
+            assert!(generate(&constants, players, 32, 32).is_err());
+        }
+        assert!(generate(&constants, 4, 1, 1).is_err());
+    }
+}
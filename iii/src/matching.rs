@@ -0,0 +1,240 @@
+use std::cmp;
+use std::collections::BinaryHeap;
+
+use fnv::FnvHashMap;
+
+/// A column and the cost of assigning it to some row.
+pub type Candidate = (usize, usize);
+
+/// The `k` cheapest columns of a dense cost row, skipping impossible
+/// (`usize::MAX`) entries.
+///
+/// Keeping at least as many candidates per row as there are rows loses
+/// nothing: a row assigned outside its own top `rows` could always swap
+/// to one of them that nobody else took. With fewer candidates than rows
+/// the result is no longer exact, and some rows may go unassigned.
+pub fn candidates(costs: &[usize], k: usize) -> Vec<Candidate> {
+    let mut candidates = costs.iter()
+        .cloned()
+        .enumerate()
+        .filter(|(_, cost)| *cost != usize::MAX)
+        .collect::<Vec<_>>();
+
+    if candidates.len() > k {
+        candidates.select_nth_unstable_by_key(k, |(column, cost)| (*cost, *column));
+        candidates.truncate(k);
+    }
+
+    candidates
+}
+
+/// Minimum-cost assignment of rows to distinct columns, given each row's
+/// candidate columns.
+///
+/// Rows are matched one at a time along shortest augmenting paths, with
+/// potentials keeping reduced costs non-negative for Dijkstra. As many rows
+/// as possible are matched; `result[row]` is the column assigned to `row`,
+/// or `None` if it's left out.
+pub fn minimize(rows: &[Vec<Candidate>]) -> Vec<Option<usize>> {
+
+    // Dense indices for every column that appears anywhere
+    let mut index = FnvHashMap::default();
+    let mut columns = Vec::new();
+    let mut edges = rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|(column, cost)| {
+                    let j = *index.entry(*column).or_insert_with(|| {
+                        columns.push(*column);
+                        columns.len() - 1
+                    });
+                    (j, *cost as i64)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Every row may also go unassigned, at a cost no real assignment can
+    // make up for, so the most rows possible end up matched
+    let real = columns.len();
+    let most = rows.iter()
+        .flat_map(|row| row.iter().map(|(_, cost)| *cost as i64))
+        .max()
+        .unwrap_or(0);
+    let unassigned = (most + 1) * (rows.len() as i64 + 1);
+    for (i, row) in edges.iter_mut().enumerate() {
+        row.push((real + i, unassigned));
+    }
+
+    // Real columns first, then one unassigned slot per row
+    let width = real + rows.len();
+    let mut row_potential = vec![0i64; rows.len()];
+    let mut column_potential = vec![0i64; width];
+    let mut row_match: Vec<Option<usize>> = vec![None; rows.len()];
+    let mut column_match: Vec<Option<usize>> = vec![None; width];
+
+    let mut dist = vec![i64::MAX; width];
+    let mut pred = vec![0; width];
+    let mut done = vec![false; width];
+    let mut touched = Vec::new();
+
+    for source in 0..rows.len() {
+
+        let mut queue = BinaryHeap::new();
+        let mut settled = Vec::new();
+        let mut free = None;
+
+        for (j, cost) in &edges[source] {
+            let reduced = cost - row_potential[source] - column_potential[*j];
+            if reduced < dist[*j] {
+                if dist[*j] == i64::MAX { touched.push(*j); }
+                dist[*j] = reduced;
+                pred[*j] = source;
+                queue.push(cmp::Reverse((reduced, *j)));
+            }
+        }
+
+        while let Some(cmp::Reverse((d, j))) = queue.pop() {
+            if done[j] || d > dist[j] { continue }
+            done[j] = true;
+            settled.push(j);
+
+            let row = match column_match[j] {
+            | None => { free = Some(j); break }
+            | Some(row) => row,
+            };
+
+            for (k, cost) in &edges[row] {
+                if done[*k] { continue }
+                let next = d + cost - row_potential[row] - column_potential[*k];
+                if next < dist[*k] {
+                    if dist[*k] == i64::MAX { touched.push(*k); }
+                    dist[*k] = next;
+                    pred[*k] = row;
+                    queue.push(cmp::Reverse((next, *k)));
+                }
+            }
+        }
+
+        if let Some(end) = free {
+            // Shift potentials so the augmenting path is tight and every
+            // other reduced cost stays non-negative
+            let delta = dist[end];
+            row_potential[source] += delta;
+            for j in &settled {
+                let slack = delta - dist[*j];
+                column_potential[*j] -= slack;
+                if let Some(row) = column_match[*j] {
+                    row_potential[row] += slack;
+                }
+            }
+
+            let mut j = end;
+            loop {
+                let row = pred[j];
+                let prev = row_match[row];
+                column_match[j] = Some(row);
+                row_match[row] = Some(j);
+                match prev {
+                | Some(prev) if row != source => j = prev,
+                | _ => break,
+                }
+            }
+        }
+
+        for j in touched.drain(..) {
+            dist[j] = i64::MAX;
+            done[j] = false;
+        }
+    }
+
+    row_match.into_iter()
+        .map(|j| j.and_then(|j| columns.get(j).cloned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Deterministic xorshift, so failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Most rows matched, then least total cost, over every assignment.
+    fn brute(rows: &[Vec<Candidate>], row: usize, taken: &mut Vec<usize>) -> (usize, usize) {
+        if row == rows.len() { return (0, 0) }
+        let mut best = brute(rows, row + 1, taken);
+        for (column, cost) in &rows[row] {
+            if taken.contains(column) { continue }
+            taken.push(*column);
+            let (matched, total) = brute(rows, row + 1, taken);
+            taken.pop();
+            if (matched + 1, usize::MAX - (total + cost)) > (best.0, usize::MAX - best.1) {
+                best = (matched + 1, total + cost);
+            }
+        }
+        best
+    }
+
+    /// Rows matched and total cost of `assignment`, checking it's valid.
+    fn score(rows: &[Vec<Candidate>], assignment: &[Option<usize>]) -> (usize, usize) {
+        assert_eq!(assignment.len(), rows.len());
+        let mut used = Vec::new();
+        let mut total = 0;
+        for (row, column) in rows.iter().zip(assignment) {
+            if let Some(column) = column {
+                assert!(!used.contains(column), "column {} assigned twice", column);
+                used.push(*column);
+                total += row.iter()
+                    .find(|(candidate, _)| candidate == column)
+                    .map(|(_, cost)| *cost)
+                    .expect("assigned a column that isn't a candidate");
+            }
+        }
+        (used.len(), total)
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let (height, width) = (rng.below(6), 1 + rng.below(6));
+            let mut rows = vec![Vec::new(); height];
+            for row in &mut rows {
+                for column in 0..width {
+                    if rng.below(4) != 0 {
+                        row.push((column * 7, rng.below(50)));
+                    }
+                }
+            }
+            let assignment = minimize(&rows);
+            assert_eq!(score(&rows, &assignment), brute(&rows, 0, &mut Vec::new()), "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn handles_empty_inputs() {
+        assert!(minimize(&[]).is_empty());
+        assert_eq!(minimize(&[Vec::new(), Vec::new()]), vec![None, None]);
+        assert_eq!(minimize(&[Vec::new(), vec![(3, 5)]]), vec![None, Some(3)]);
+    }
+
+    #[test]
+    fn keeps_the_cheapest_candidates() {
+        let costs = [9, usize::MAX, 1, 4, 1, 7];
+        let mut kept = candidates(&costs, 3);
+        kept.sort();
+        assert_eq!(kept, vec![(2, 1), (3, 4), (4, 1)]);
+        assert!(candidates(&[usize::MAX; 4], 2).is_empty());
+    }
+}
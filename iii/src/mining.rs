@@ -0,0 +1,136 @@
+use data::Ship;
+use grid::{Grid, Pos};
+use profile::Profile;
+
+/// What mining a cell is worth, independent of which ship does it.
+#[derive(Clone, Debug)]
+struct Cell {
+    /// Halite an empty ship gains mining here for `1..=max_mine` turns
+    gains: Vec<usize>,
+    /// Halite a ship pays to move off after mining here that many turns
+    leave: Vec<usize>,
+    /// Turns from here to the nearest deposit point
+    home: usize,
+}
+
+/// Halite-per-turn estimates that decide where ships mine and when they
+/// head home.
+///
+/// Mining a cell is worth the halite it adds to a ship's cargo over the
+/// extra turns the detour costs compared to heading straight home, for
+/// however many turns of mining pay best. A ship heads home once nothing
+/// nearby beats the rate it could make starting over from a deposit point.
+#[derive(Debug)]
+pub struct Policy {
+    width: usize,
+    capacity: usize,
+    /// `Params::mining_search`
+    search: usize,
+    /// Per cell, in row-major order, or `None` where we won't mine
+    cells: Vec<Option<Cell>>,
+    /// Rate an empty ship can expect from a round trip out of a deposit point
+    fresh: f64,
+}
+
+impl Policy {
+    /// `ships` is the size of our fleet: a fresh trip can only count on one
+    /// of the best couple of cells per ship, since the rest will be taken.
+    pub fn new(grid: &Grid, profile: &Profile, width: usize, height: usize, ships: usize) -> Self {
+        let rules = *grid.rules();
+        let mut cells = Vec::with_capacity(width * height);
+
+        grid.fill(&mut cells, |grid, pos, halite| {
+            if grid.is_deposit(pos) || grid.is_capture_threat(pos)
+            || !((profile.params.inspiration && grid.is_inspired(pos)) || grid.enemies_around(pos, profile.params.mining_enemy_radius) == 0) {
+                return None
+            }
+
+            let inspired = grid.is_inspired(pos);
+            let mut cell = Cell {
+                gains: Vec::with_capacity(profile.params.max_mine),
+                leave: Vec::with_capacity(profile.params.max_mine),
+                home: grid.distance_from_deposit(pos),
+            };
+            let (mut left, mut total) = (halite, 0);
+            for _ in 0..profile.params.max_mine {
+                let (extracted, gained) = rules.extract(left, total, inspired);
+                left -= extracted;
+                total += gained;
+                cell.gains.push(total);
+                cell.leave.push(rules.move_cost(left, inspired));
+            }
+
+            if cell.gains[0] == 0 { None } else { Some(cell) }
+        });
+
+        let mut trips = cells.iter()
+            .flatten()
+            .map(|cell| {
+                cell.gains.iter()
+                    .zip(&cell.leave)
+                    .enumerate()
+                    .map(|(turn, (gained, leave))| {
+                        let added = gained.saturating_sub(*leave);
+                        added as f64 / (2 * cell.home + turn + 1) as f64
+                    })
+                    .fold(0.0, f64::max)
+            })
+            .collect::<Vec<_>>();
+        trips.sort_by(|a, b| b.total_cmp(a));
+        let fresh = trips.get(usize::min(profile.params.fresh_cells_per_ship * ships, trips.len().saturating_sub(1)))
+            .cloned()
+            .unwrap_or(0.0);
+
+        Policy { width, capacity: rules.capacity(), search: profile.params.mining_search, cells, fresh }
+    }
+
+    /// Best halite per extra turn `ship` could add by mining `pos` on its
+    /// way home.
+    pub fn rate(&self, grid: &Grid, ship: &Ship, pos: Pos) -> Option<f64> {
+        let start = Pos(ship.x, ship.y);
+        if pos != start && grid.is_stuck(pos) { return None }
+        let cell = self.cells[pos.1 * self.width + pos.0].as_ref()?;
+
+        let room = self.capacity.saturating_sub(ship.halite);
+        let distance = grid.dist(start, pos);
+        let detour = (distance + cell.home).saturating_sub(grid.distance_from_deposit(start));
+        let setout = if distance > 0 { grid.move_cost(start) } else { 0 };
+        let mut best = None;
+
+        for (turn, (gained, leave)) in cell.gains.iter().zip(&cell.leave).enumerate() {
+            let gained = usize::min(room, *gained);
+            let added = gained.saturating_sub(setout + leave);
+            let rate = added as f64 / (detour + turn + 1) as f64;
+            if best.is_none_or(|best| rate > best) {
+                best = Some(rate);
+            }
+            if gained == room { break }
+        }
+
+        best
+    }
+
+    /// Cost for assigning `ship` to mine `pos`, lower for better rates.
+    pub fn cost(&self, grid: &Grid, ship: &Ship, pos: Pos) -> usize {
+        match self.rate(grid, ship, pos) {
+        | Some(rate) => ((self.capacity as f64 - rate) * 100.0) as usize,
+        | None => usize::MAX,
+        }
+    }
+
+    /// Whether `ship` does better taking its cargo home now than mining
+    /// nearby first: the trip home has to pay at least as well as a fresh
+    /// trip would, and no cell within `search` can be worth the detour.
+    pub fn should_return(&self, grid: &Grid, ship: &Ship) -> bool {
+        if ship.halite >= self.capacity { return true }
+
+        // Carrying too little to be worth the trip on its own
+        let start = Pos(ship.x, ship.y);
+        let home = ship.halite.saturating_sub(grid.move_cost(start)) as f64
+            / usize::max(grid.distance_from_deposit(start), 1) as f64;
+        if home < self.fresh { return false }
+
+        !grid.around(start, self.search)
+            .any(|pos| self.rate(grid, ship, pos).is_some_and(|rate| rate > self.fresh))
+    }
+}
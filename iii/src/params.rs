@@ -0,0 +1,268 @@
+use std::env;
+use std::fs;
+
+use failure;
+use serde_json;
+
+/// Environment variable that may hold parameters as JSON
+pub const PARAMS_VAR: &str = "MY_BOT_PARAMS";
+
+/// Tuning parameters for the `Executor`, so experiments can change them
+/// without a recompile.
+///
+/// Fields left out of a JSON object keep their defaults, and unknown fields
+/// are rejected so a typo doesn't silently test the defaults.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Params {
+
+    /// Milliseconds we allow ourselves per turn, well under the engine's limit
+    pub budget: u64,
+    /// Percentage of the budget that target assignment may run into
+    pub assign_share: u32,
+    /// Percentage of the budget after which paths are no longer searched
+    pub plan_share: u32,
+    /// Cheapest cells kept per ship for target assignment
+    pub candidates: usize,
+    /// Allies around a deposit point past which returning ships wait their turn
+    pub crowd_limit: usize,
+    /// Radius around a deposit point within which allies count towards its crowd
+    pub crowd_radius: usize,
+    /// Closest a returning ship waits to a crowded deposit point
+    pub crowd_wait_min: usize,
+    /// Farthest a returning ship waits from a crowded deposit point
+    pub crowd_wait_max: usize,
+
+    /// `Profile::spawn_margin` in two-player games
+    pub spawn_margin_2p: f64,
+    /// `Profile::spawn_margin` in larger games
+    pub spawn_margin_4p: f64,
+    /// `Profile::max_risk` in two-player games
+    pub max_risk_2p: f64,
+    /// `Profile::max_risk` in larger games
+    pub max_risk_4p: f64,
+    /// `Profile::ships_per_drop` on maps smaller than `large_map` square
+    pub ships_per_drop_small: usize,
+    /// `Profile::ships_per_drop` on larger maps
+    pub ships_per_drop_large: usize,
+    /// Side of the smallest square map that counts as large
+    pub large_map: usize,
+
+    /// Path cost of moving through an ally when starting on a deposit point
+    pub yard_crowd_cost: usize,
+    /// Turns ahead that allies reserve cells along their planned paths
+    pub window: usize,
+
+    /// Whether to ram loaded enemy ships
+    pub attack: bool,
+    /// Least enemy cargo worth ramming
+    pub attack_min_cargo: usize,
+    /// Least expected gain, in halite, for an attack to go ahead
+    pub attack_min_gain: f64,
+    /// Radius within which ships race to pick up the halite spilled by a collision
+    pub attack_radius: usize,
+
+    /// Stop building dropoffs this many rounds before the game ends
+    pub drop_last_rounds: usize,
+    /// Closest a new dropoff may be to one of our deposit points
+    pub drop_min_distance: usize,
+    /// Closest a new dropoff may be to an enemy shipyard or dropoff
+    pub drop_enemy_distance: usize,
+    /// Radius used to measure halite density around a candidate site
+    pub drop_radius: usize,
+    /// Density lost per unit of distance beyond `drop_min_distance`
+    pub drop_distance_penalty: usize,
+    /// Minimum density around a site, as a multiple of `DROPOFF_COST`
+    pub drop_density_ratio: usize,
+
+    /// Most turns we plan on mining a single cell
+    pub max_mine: usize,
+    /// Radius searched for a better cell before a ship decides to head home
+    pub mining_search: usize,
+    /// Whether to mine next to enemies when it would inspire us
+    pub inspiration: bool,
+    /// Radius within which enemies keep us from mining an uninspired cell
+    pub mining_enemy_radius: usize,
+    /// Best cells per ship a fresh trip can't count on, since they'll be taken
+    pub fresh_cells_per_ship: usize,
+
+    /// Cargo, as a fraction of capacity, past which we expect an enemy to head home
+    pub return_ratio: f64,
+    /// Chance that a returning enemy takes one of the moves that brings it closer
+    pub return_focus: f64,
+    /// Halite under a mining enemy at which it's as likely to stay as to move
+    pub stay_halite: f64,
+    /// Halite a cell counts as having when weighing where a mining enemy moves
+    pub move_halite: f64,
+
+    /// Turns of slack on top of each ship's scheduled trip home
+    pub recall_slack: usize,
+    /// Ships a deposit point can take in on the last turn, crashing in from every side
+    pub recall_throughput: usize,
+
+    /// Turns of deposits that observed income is averaged over
+    pub spawn_window: usize,
+    /// Ship-turns of assumed income blended into the observed average
+    pub spawn_prior_weight: f64,
+    /// Turns an empty ship takes to fill up and bring its cargo home on a fresh map
+    pub spawn_prior_trip: f64,
+    /// Turns a new ship spends reaching its first target, and its last getting home
+    pub spawn_ramp: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            budget: 1500,
+            assign_share: 50,
+            plan_share: 85,
+            candidates: 64,
+            crowd_limit: 9,
+            crowd_radius: 2,
+            crowd_wait_min: 3,
+            crowd_wait_max: 5,
+
+            spawn_margin_2p: 1.0,
+            spawn_margin_4p: 1.5,
+            max_risk_2p: 0.5,
+            max_risk_4p: 0.3,
+            ships_per_drop_small: 12,
+            ships_per_drop_large: 10,
+            large_map: 56,
+
+            yard_crowd_cost: 1000000,
+            window: 8,
+
+            attack: true,
+            attack_min_cargo: 300,
+            attack_min_gain: 50.0,
+            attack_radius: 4,
+
+            drop_last_rounds: 100,
+            drop_min_distance: 10,
+            drop_enemy_distance: 6,
+            drop_radius: 5,
+            drop_distance_penalty: 200,
+            drop_density_ratio: 3,
+
+            max_mine: 8,
+            mining_search: 8,
+            inspiration: true,
+            mining_enemy_radius: 2,
+            fresh_cells_per_ship: 2,
+
+            return_ratio: 0.75,
+            return_focus: 0.8,
+            stay_halite: 100.0,
+            move_halite: 50.0,
+
+            recall_slack: 3,
+            recall_throughput: 4,
+
+            spawn_window: 50,
+            spawn_prior_weight: 200.0,
+            spawn_prior_trip: 40.0,
+            spawn_ramp: 10,
+        }
+    }
+}
+
+impl Params {
+    /// Parameters from the JSON file at `path` if given, otherwise from the
+    /// `MY_BOT_PARAMS` environment variable if set, otherwise the defaults.
+    pub fn load(path: Option<&str>) -> Result<Self, failure::Error> {
+        let json = match (path, env::var(PARAMS_VAR)) {
+        | (Some(path), _) => fs::read_to_string(path)?,
+        | (None, Ok(json)) => json,
+        | (None, Err(_)) => return Ok(Params::default()),
+        };
+        Params::parse(&json)
+    }
+
+    /// Parameters from a JSON object, rejecting any the `Executor` can't
+    /// work with.
+    fn parse(json: &str) -> Result<Self, failure::Error> {
+        let params = serde_json::from_str::<Params>(json)?;
+        let problem = if params.candidates == 0 {
+            "candidates must be at least 1"
+        } else if params.assign_share > 100 || params.plan_share > 100 {
+            "assign_share and plan_share are percentages of the budget, at most 100"
+        } else if params.crowd_wait_min > params.crowd_wait_max {
+            "crowd_wait_min must not exceed crowd_wait_max"
+        } else if params.crowd_wait_min <= params.crowd_radius {
+            "crowd_wait_min must be past crowd_radius, or waiting ships crowd the deposit themselves"
+        } else if params.window == 0 || params.max_mine == 0 {
+            "window and max_mine must be at least 1"
+        } else if params.stay_halite <= 0.0 || params.move_halite <= 0.0 {
+            "stay_halite and move_halite must be positive, or enemy moves on empty cells can't be weighed"
+        } else if params.spawn_prior_weight <= 0.0 || params.spawn_prior_trip <= 0.0 {
+            "spawn_prior_weight and spawn_prior_trip must be positive, or a ship's value is undefined"
+        } else {
+            return Ok(params)
+        };
+        Err(failure::err_msg(format!("invalid parameters: {}", problem)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::fs;
+
+    use serde_json::{self, Value};
+
+    use super::{Params, PARAMS_VAR};
+
+    fn json(params: &Params) -> Value {
+        serde_json::to_value(params).unwrap()
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let params = Params::parse(r#"{ "budget": 900, "attack": false }"#).unwrap();
+        let expected = Params { budget: 900, attack: false, ..Params::default() };
+        assert_eq!(json(&params), json(&expected));
+        assert_eq!(json(&Params::parse("{}").unwrap()), json(&Params::default()));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(Params::parse(r#"{ "budgte": 900 }"#).is_err());
+    }
+
+    #[test]
+    fn unworkable_values_are_rejected() {
+        assert!(Params::parse(r#"{ "candidates": 0 }"#).is_err());
+        assert!(Params::parse(r#"{ "assign_share": 101 }"#).is_err());
+        assert!(Params::parse(r#"{ "plan_share": 101 }"#).is_err());
+        assert!(Params::parse(r#"{ "crowd_wait_min": 6, "crowd_wait_max": 5 }"#).is_err());
+        assert!(Params::parse(r#"{ "crowd_radius": 3 }"#).is_err());
+        assert!(Params::parse(r#"{ "window": 0 }"#).is_err());
+        assert!(Params::parse(r#"{ "max_mine": 0 }"#).is_err());
+        assert!(Params::parse(r#"{ "stay_halite": 0.0 }"#).is_err());
+        assert!(Params::parse(r#"{ "move_halite": -1.0 }"#).is_err());
+        assert!(Params::parse(r#"{ "spawn_prior_weight": 0.0 }"#).is_err());
+        assert!(Params::parse(r#"{ "spawn_prior_trip": 0.0 }"#).is_err());
+        assert!(Params::parse(r#"{ "plan_share": 100, "crowd_wait_min": 5 }"#).is_ok());
+    }
+
+    #[test]
+    fn bad_sources_are_errors() {
+        let dir = env::temp_dir();
+        let missing = dir.join("my_bot-params-missing.json");
+        let malformed = dir.join("my_bot-params-malformed.json");
+        fs::write(&malformed, "{ \"budget\": ").unwrap();
+
+        assert!(Params::load(missing.to_str()).is_err());
+        assert!(Params::load(malformed.to_str()).is_err());
+
+        // The only test that touches the variable
+        env::set_var(PARAMS_VAR, "{ \"budget\": -1 }");
+        let from_env = Params::load(None);
+        env::remove_var(PARAMS_VAR);
+        assert!(from_env.is_err());
+
+        fs::remove_file(malformed).unwrap();
+    }
+}
@@ -1,115 +1,412 @@
+use std::fmt;
 use std::str::FromStr;
+use std::vec;
 
+use failure::Fail;
+
+use command::Command;
 use data;
+use grid::Dir;
+
+/// Most players a frame may declare.
+const MAX_PLAYERS: usize = 16;
+
+/// Longest side a frame's map may declare.
+const MAX_SIDE: usize = 1024;
 
-fn next<'a, S: Iterator<Item = &'a str>>(stream: &mut S) -> usize {
-    stream.next()
-        .and_then(|x| usize::from_str(x).ok())
-        .expect("[INTERNAL ERROR]: expected usize")
+/// A frame from the engine that doesn't match the protocol. Lines are
+/// numbered from 1 within the frame being read.
+#[derive(Debug)]
+pub enum ParseError {
+    MissingLine { line: usize, field: &'static str },
+    MissingToken { line: usize, field: &'static str },
+    InvalidToken { line: usize, field: &'static str, token: String },
 }
 
-impl data::State {
-    pub fn initialize<S: Iterator<Item = String>>(stream: &mut S) -> Self {
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+        | ParseError::MissingLine { line, field } => write!(fmt, "line {}: frame ended before {}", line, field),
+        | ParseError::MissingToken { line, field } => write!(fmt, "line {}: missing {}", line, field),
+        | ParseError::InvalidToken { line, field, token } => write!(fmt, "line {}: invalid {} {:?}", line, field, token),
+        }
+    }
+}
+
+impl Fail for ParseError {}
+
+/// Lines of the frame being read, counted for error reporting.
+struct Frame<'s, S: 's> {
+    stream: &'s mut S,
+    line: usize,
+    /// Line read ahead of time, to be returned next
+    ahead: Option<Tokens>,
+}
 
-        macro_rules! split {
-            (|$s:ident| $e:expr) => {{
-                let line = stream.next().expect("[INTERNAL ERROR]: invalid initialization data");
-                let mut $s = line.split_whitespace();
-                $e
-            }}
+impl<'s, S: Iterator<Item = String>> Frame<'s, S> {
+    fn new(stream: &'s mut S) -> Self {
+        Frame { stream, line: 0, ahead: None }
+    }
+
+    /// Tokens of the next line, which should hold `field`.
+    fn next(&mut self, field: &'static str) -> Result<Tokens, ParseError> {
+        if let Some(tokens) = self.ahead.take() {
+            return Ok(tokens)
         }
+        self.line += 1;
+        let line = self.line;
+        let text = self.stream.next().ok_or(ParseError::MissingLine { line, field })?;
+        let tokens = text.split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .into_iter();
+        Ok(Tokens { line, tokens })
+    }
+
+    /// Return `tokens` again from the next call to `next`.
+    fn back(&mut self, tokens: Tokens) {
+        self.ahead = Some(tokens);
+    }
+}
+
+#[derive(Clone)]
+struct Tokens {
+    line: usize,
+    tokens: vec::IntoIter<String>,
+}
+
+impl Tokens {
+    /// Whether the rest of the line is `count` numbers, the first below `limit`.
+    fn fits(&self, count: usize, limit: usize) -> bool {
+        let mut tokens = self.clone();
+        tokens.tokens.len() == count
+        && tokens.below("", limit).is_ok()
+        && (1..count).all(|_| tokens.next("").is_ok())
+    }
 
-        let (num_players, id) = split!(|s| { (next(&mut s), next(&mut s)) });
+    fn next(&mut self, field: &'static str) -> Result<usize, ParseError> {
+        let line = self.line;
+        let token = self.tokens.next().ok_or(ParseError::MissingToken { line, field })?;
+        usize::from_str(&token).map_err(|_| ParseError::InvalidToken { line, field, token })
+    }
+
+    /// Next token, which must be less than `limit`.
+    fn below(&mut self, field: &'static str, limit: usize) -> Result<usize, ParseError> {
+        let value = self.next(field)?;
+        if value < limit {
+            Ok(value)
+        } else {
+            Err(ParseError::InvalidToken { line: self.line, field, token: value.to_string() })
+        }
+    }
+}
+
+impl data::State {
+    pub fn initialize<S: Iterator<Item = String>>(stream: &mut S) -> Result<Self, ParseError> {
+
+        let mut frame = Frame::new(stream);
+
+        let mut tokens = frame.next("player count")?;
+        let num_players = tokens.below("player count", MAX_PLAYERS + 1)?;
+        let id = tokens.below("player ID", num_players)?;
         let round = 0;
         let drops = Vec::with_capacity(0);
         let ships = Vec::with_capacity(0);
-        let mut scores = vec![0; num_players];
+        let scores = vec![0; num_players];
         let mut yards = vec![data::Shipyard{ owner: 0, x: 0, y: 0 }; num_players];
+        let mut lines = vec![0; num_players];
 
         // Initialize players
         for _ in 0..num_players {
-            split!(|s| {
-                let id = next(&mut s);
-                let x = next(&mut s);
-                let y = next(&mut s);
-                scores[id] = 0;
-                yards[id] = data::Shipyard{ owner: id, x, y };
-            });
+            let mut tokens = frame.next("shipyard")?;
+            let id = tokens.below("player ID", num_players)?;
+            let x = tokens.next("shipyard x")?;
+            let y = tokens.next("shipyard y")?;
+            yards[id] = data::Shipyard{ owner: id, x, y };
+            lines[id] = tokens.line;
         }
 
         // Initialize map data
-        let (width, height) = split!(|s| (next(&mut s), next(&mut s)));
-        let mut halite = Vec::with_capacity(width * height);
+        let mut tokens = frame.next("map size")?;
+        let width = tokens.below("map width", MAX_SIDE + 1)?;
+        let height = tokens.below("map height", MAX_SIDE + 1)?;
+        let cells = width.checked_mul(height).ok_or_else(|| {
+            let token = format!("{} {}", width, height);
+            ParseError::InvalidToken { line: tokens.line, field: "map size", token }
+        })?;
+        let mut halite = Vec::with_capacity(cells);
         for _ in 0..height {
-            split!(|s| {
-                for _ in 0..width {
-                    halite.push(next(&mut s));
-                }
-            });
+            let mut tokens = frame.next("map row")?;
+            for _ in 0..width {
+                halite.push(tokens.next("halite")?);
+            }
         }
 
-        data::State { id, width, height, round, scores, drops, ships, yards, halite }
+        // Shipyards come before the map size, so check them once it's known
+        for (yard, line) in yards.iter().zip(lines) {
+            if yard.x >= width || yard.y >= height {
+                let token = format!("{} {}", yard.x, yard.y);
+                return Err(ParseError::InvalidToken { line, field: "shipyard", token })
+            }
+        }
+
+        Ok(data::State { id, width, height, round, scores, drops, ships, yards, halite, next_ship: 0 })
     }
 
-    pub fn update<S: Iterator<Item = String>>(&mut self, stream: &mut S) {
+    /// Read the next turn's frame. On error the state is left as it was.
+    ///
+    /// A malformed ship, dropoff or cell line is skipped so the rest of the
+    /// frame is still consumed. A malformed count leaves the frame's length
+    /// unknown, so the next call first skips ahead to a line that can be the
+    /// round header of a later frame.
+    pub fn update<S: Iterator<Item = String>>(&mut self, stream: &mut S) -> Result<(), ParseError> {
 
-        macro_rules! split {
-            (|$s:ident| $e:expr) => {{
-                let line = stream.next().expect("[INTERNAL ERROR]: invalid update data");
-                let mut $s = line.split_whitespace();
-                $e
-            }}
-        }
+        let mut frame = Frame::new(stream);
+        let players = self.scores.len();
+        let (width, height) = (self.width, self.height);
 
-        // Clear outdated ships and dropoffs
-        self.ships = Vec::new();
-        self.drops = Vec::new();
-        self.round = split!(|s| next(&mut s));
+        let mut ships = Vec::new();
+        let mut drops = Vec::new();
+        let mut scores = self.scores.clone();
+        let mut error = None;
 
-        // Player updates
-        for _ in 0..self.scores.len() {
+        // Lines left over from a frame we couldn't follow: the round header
+        // is the only lone number followed by a player line
+        let mut skipped = 0;
+        let round = loop {
+            let mut tokens = frame.next("round")?;
+            let round = match tokens.next("round") {
+            | Ok(round) if round > self.round && tokens.tokens.len() == 0 => round,
+            | _ => { skipped += 1; continue }
+            };
+            let player = frame.next("player")?;
+            let fits = player.fits(4, players);
+            frame.back(player);
+            if fits { break round }
+            skipped += 1;
+        };
 
-            let (player, num_ships, num_dropoffs, score) = split!(|s| {
-                (next(&mut s), next(&mut s), next(&mut s), next(&mut s))    
-            });
+        if skipped > 0 {
+            warn!("[{}]: skipped {} lines to resynchronize", round, skipped);
+        }
+
+        // Player updates
+        for _ in 0..players {
 
-            self.scores[player] = score;
+            let mut tokens = frame.next("player")?;
+            let player = tokens.below("player ID", players)?;
+            let num_ships = tokens.next("ship count")?;
+            let num_dropoffs = tokens.next("dropoff count")?;
+            scores[player] = tokens.next("score")?;
 
             // Ship updates
             for _ in 0..num_ships {
-                split!(|s| {
-                    self.ships.push(data::Ship {
-                        owner:  player,
-                        id:     next(&mut s),
-                        x:      next(&mut s),
-                        y:      next(&mut s),
-                        halite: next(&mut s),
-                    });
-                });
+                let mut tokens = frame.next("ship")?;
+                let ship = (|| Ok(data::Ship {
+                    owner:  player,
+                    id:     tokens.next("ship ID")?,
+                    x:      tokens.below("ship x", width)?,
+                    y:      tokens.below("ship y", height)?,
+                    halite: tokens.next("ship halite")?,
+                }))();
+                keep(ship, &mut ships, &mut error);
             }
 
             // Dropoff updates
             for _ in 0..num_dropoffs {
-                split!(|s| {
-                    let _ = next(&mut s);
-                    self.drops.push(data::Dropoff {
+                let mut tokens = frame.next("dropoff")?;
+                let drop = (|| {
+                    let _ = tokens.next("dropoff ID")?;
+                    Ok(data::Dropoff {
                         owner: player,
-                        x: next(&mut s),
-                        y: next(&mut s),
-                    });
-                });
+                        x: tokens.below("dropoff x", width)?,
+                        y: tokens.below("dropoff y", height)?,
+                    })
+                })();
+                keep(drop, &mut drops, &mut error);
             }
         }
 
         // Map updates
-        let num_updates = split!(|s| next(&mut s));
+        let num_updates = frame.next("cell count")?.next("cell count")?;
+        // The count is untrusted, but no frame changes more cells than there are
+        let mut cells = Vec::with_capacity(usize::min(num_updates, width * height));
         for _ in 0..num_updates {
-            split!(|s| {
-                let x = next(&mut s);
-                let y = next(&mut s);
-                let halite = next(&mut s);
-                self.halite[y * self.width + x] = halite;
-            });
+            let mut tokens = frame.next("cell")?;
+            let cell = (|| {
+                let x = tokens.below("cell x", width)?;
+                let y = tokens.below("cell y", height)?;
+                Ok((y * width + x, tokens.next("cell halite")?))
+            })();
+            keep(cell, &mut cells, &mut error);
+        }
+
+        if let Some(error) = error {
+            return Err(error)
+        }
+
+        self.next_ship = ships.iter()
+            .map(|ship| ship.id + 1)
+            .fold(self.next_ship, usize::max);
+        self.round = round;
+        self.scores = scores;
+        self.ships = ships;
+        self.drops = drops;
+        for (index, halite) in cells {
+            self.halite[index] = halite;
+        }
+
+        Ok(())
+    }
+}
+
+/// Push `item` if it parsed, otherwise remember the first error.
+fn keep<T>(item: Result<T, ParseError>, items: &mut Vec<T>, error: &mut Option<ParseError>) {
+    match item {
+    | Ok(item) => items.push(item),
+    | Err(item) => { error.get_or_insert(item); }
+    }
+}
+
+/// Parse a bot's command line, skipping any malformed commands.
+pub fn commands(line: &str) -> Vec<Command> {
+    let mut tokens = line.split_whitespace();
+    let mut commands = Vec::new();
+    while let Some(token) = tokens.next() {
+        let command = match token {
+        | "g" => Some(Command::Spawn),
+        | "c" => id(&mut tokens).map(Command::Transform),
+        | "m" => {
+            let id = id(&mut tokens);
+            let dir = match tokens.next() {
+            | Some("n") => Some(Dir::N),
+            | Some("s") => Some(Dir::S),
+            | Some("e") => Some(Dir::E),
+            | Some("w") => Some(Dir::W),
+            | Some("o") => Some(Dir::O),
+            | _         => None,
+            };
+            id.and_then(|id| dir.map(|dir| Command::Move(id, dir)))
+        }
+        | _ => None,
+        };
+        commands.extend(command);
+    }
+    commands
+}
+
+fn id<'a, S: Iterator<Item = &'a str>>(stream: &mut S) -> Option<usize> {
+    stream.next().and_then(|x| usize::from_str(x).ok())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.trim().to_string()).collect()
+    }
+
+    fn state() -> data::State {
+        let init = lines("2 0
+            0 0 0
+            1 2 2
+            4 4
+            10 10 10 10
+            10 10 10 10
+            10 10 10 10
+            10 10 10 10");
+        data::State::initialize(&mut init.into_iter()).unwrap()
+    }
+
+    /// Turn 2, with one ship each and a mined cell.
+    const GOOD: &str = "2
+        0 1 0 4000
+        0 1 0 5
+        1 1 0 4000
+        1 2 2 7
+        1
+        1 0 8";
+
+    #[test]
+    fn bad_item_line_skips_only_that_frame() {
+        let mut state = state();
+        let mut stream = lines("1
+            0 1 0 4000
+            0 9 0 5
+            1 0 0 5000
+            1
+            1 0 8")
+            .into_iter()
+            .chain(lines(GOOD))
+            .peekable();
+
+        match state.update(&mut stream) {
+        | Err(ParseError::InvalidToken { field: "ship x", .. }) => (),
+        | other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(state.round, 0);
+
+        state.update(&mut stream).unwrap();
+        assert_eq!(state.round, 2);
+        assert_eq!(state.ships.len(), 2);
+        assert_eq!(state.scores, vec![4000, 4000]);
+        assert_eq!(state.halite[1], 8);
+        assert!(stream.peek().is_none());
+    }
+
+    #[test]
+    fn bad_count_line_resynchronizes_on_the_next_frame() {
+        let mut state = state();
+        let mut stream = lines("1
+            0 1 0 4000
+            0 1 0 5
+            1 x 0 5000
+            1
+            1 0 8")
+            .into_iter()
+            .chain(lines(GOOD))
+            .peekable();
+
+        match state.update(&mut stream) {
+        | Err(ParseError::InvalidToken { field: "ship count", .. }) => (),
+        | other => panic!("unexpected {:?}", other),
+        }
+
+        state.update(&mut stream).unwrap();
+        assert_eq!(state.round, 2);
+        assert_eq!(state.ships.iter().map(|ship| (ship.owner, ship.id)).collect::<Vec<_>>(), vec![(0, 0), (1, 1)]);
+        assert_eq!(state.halite[1], 8);
+        assert!(stream.peek().is_none());
+    }
+
+    #[test]
+    fn huge_counts_are_rejected_without_allocating() {
+        let players = lines("1000000000000 0");
+        match data::State::initialize(&mut players.into_iter()) {
+        | Err(ParseError::InvalidToken { field: "player count", .. }) => (),
+        | other => panic!("unexpected {:?}", other),
+        }
+
+        let size = lines("1 0
+            0 0 0
+            4294967296 4294967296");
+        match data::State::initialize(&mut size.into_iter()) {
+        | Err(ParseError::InvalidToken { field: "map width", .. }) => (),
+        | other => panic!("unexpected {:?}", other),
+        }
+
+        let mut state = state();
+        let cells = lines("2
+            0 0 0 4000
+            1 0 0 4000
+            1000000000000
+            1 0 8");
+        match state.update(&mut cells.into_iter()) {
+        | Err(ParseError::MissingLine { field: "cell", .. }) => (),
+        | other => panic!("unexpected {:?}", other),
         }
+        assert_eq!(state.round, 0);
     }
 }
@@ -0,0 +1,145 @@
+use fnv::FnvHashSet;
+
+use data::{Ship, State};
+use grid::{DIRS, Dir};
+use params::Params;
+use rules::Rules;
+use simulate::{distance, shift};
+
+/// Chance that at least one enemy ship ends up on each cell next turn,
+/// in row-major order, given the IDs of the `inspired` ships.
+pub fn collisions(params: &Params, rules: &Rules, state: &State, inspired: &FnvHashSet<usize>) -> Vec<f64> {
+    let mut clear = vec![1.0; state.width * state.height];
+    for ship in state.enemies() {
+        let moves = moves(params, rules, state, ship, inspired.contains(&ship.id));
+        for (dir, chance) in DIRS.iter().zip(&moves) {
+            let (x, y) = shift(state.width, state.height, ship.x, ship.y, *dir);
+            clear[y * state.width + x] *= 1.0 - chance;
+        }
+    }
+    clear.into_iter()
+        .map(|clear| 1.0 - clear)
+        .collect()
+}
+
+/// Distribution over `DIRS` for an enemy ship's next move.
+///
+/// Ships that can't pay to move stay put. Loaded ships mostly head for
+/// their owner's nearest shipyard or dropoff; the rest stay on rich cells
+/// and otherwise drift toward richer neighbors.
+pub fn moves(params: &Params, rules: &Rules, state: &State, ship: &Ship, inspired: bool) -> [f64; 5] {
+    let (width, height) = (state.width, state.height);
+    let halite = |dir: Dir| {
+        let (x, y) = shift(width, height, ship.x, ship.y, dir);
+        state.halite[y * width + x] as f64
+    };
+
+    let mut chances = [0.0; 5];
+    let stay = DIRS.iter().position(|dir| *dir == Dir::O).unwrap_or(4);
+
+    if ship.halite < rules.move_cost(halite(Dir::O) as usize, inspired) {
+        chances[stay] = 1.0;
+        return chances
+    }
+
+    if ship.halite as f64 >= rules.capacity() as f64 * params.return_ratio {
+        let home = state.yards.iter()
+            .map(|yard| (yard.owner, yard.x, yard.y))
+            .chain(state.drops.iter().map(|drop| (drop.owner, drop.x, drop.y)))
+            .filter(|(owner, _, _)| *owner == ship.owner)
+            .map(|(_, x, y)| (x, y))
+            .min_by_key(|home| distance(width, height, (ship.x, ship.y), *home));
+
+        if let Some(home) = home {
+            let current = distance(width, height, (ship.x, ship.y), home);
+            let closer = DIRS.iter()
+                .map(|dir| shift(width, height, ship.x, ship.y, *dir))
+                .map(|next| distance(width, height, next, home) < current)
+                .collect::<Vec<_>>();
+            let count = closer.iter().filter(|closer| **closer).count();
+            if count > 0 {
+                for (chance, closer) in chances.iter_mut().zip(closer) {
+                    *chance = if closer {
+                        params.return_focus / count as f64
+                    } else {
+                        (1.0 - params.return_focus) / (DIRS.len() - count) as f64
+                    };
+                }
+                return chances
+            }
+        }
+    }
+
+    let here = halite(Dir::O);
+    let staying = here / (here + params.stay_halite);
+    let weights = DIRS.iter()
+        .map(|dir| if *dir == Dir::O { 0.0 } else { halite(*dir) + params.move_halite })
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+
+    for (chance, weight) in chances.iter_mut().zip(weights) {
+        *chance = (1.0 - staying) * weight / total;
+    }
+    chances[stay] = staying;
+
+    chances
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use constants::Constants;
+    use grid::{fixture, Pos};
+
+    /// An enemy carrying `halite` at (8, 8) on a map with 200 halite on
+    /// every cell, its yard to the east at (12, 8).
+    fn state(halite: usize) -> State {
+        let mut state = fixture::state(16, 200, [Pos(2, 2), Pos(12, 8)]);
+        state.ships.push(Ship { owner: 1, id: 0, x: 8, y: 8, halite });
+        state
+    }
+
+    fn predict(constants: &Constants, state: &State, inspired: bool) -> [f64; 5] {
+        moves(&Params::default(), &Rules::new(constants), state, &state.ships[0], inspired)
+    }
+
+    fn chance(moves: &[f64; 5], dir: Dir) -> f64 {
+        moves[DIRS.iter().position(|other| *other == dir).unwrap()]
+    }
+
+    #[test]
+    fn ships_that_cannot_pay_stay_put() {
+        let moves = predict(&Constants::default(), &state(19), false);
+        assert_eq!(chance(&moves, Dir::O), 1.0);
+    }
+
+    #[test]
+    fn inspired_ships_pay_the_inspired_move_cost() {
+        let constants = Constants { INSPIRED_MOVE_COST_RATIO: 20, ..Constants::default() };
+        let state = state(15);
+        assert_eq!(chance(&predict(&constants, &state, false), Dir::O), 1.0);
+        assert!(chance(&predict(&constants, &state, true), Dir::O) < 1.0);
+    }
+
+    #[test]
+    fn full_ships_head_home() {
+        let moves = predict(&Constants::default(), &state(1000), false);
+        let focus = Params::default().return_focus;
+        assert!((chance(&moves, Dir::E) - focus).abs() < 1e-9);
+        assert!(DIRS.iter()
+            .filter(|dir| **dir != Dir::E)
+            .all(|dir| chance(&moves, *dir) < chance(&moves, Dir::E)));
+    }
+
+    #[test]
+    fn every_distribution_sums_to_one() {
+        let constants = Constants::default();
+        for halite in &[0, 19, 20, 500, 749, 750, 1000] {
+            for inspired in &[false, true] {
+                let total = predict(&constants, &state(*halite), *inspired).iter().sum::<f64>();
+                assert!((total - 1.0).abs() < 1e-9, "{} halite sums to {}", halite, total);
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use params::Params;
+
+/// Decision parameters for the `Executor`, chosen per game.
+#[derive(Copy, Clone, Debug)]
+pub struct Profile {
+    /// Chance of colliding with an enemy past which a first step is ruled out
+    pub max_risk: f64,
+    /// Return a new ship must be expected to bring in, as a multiple of its cost
+    pub spawn_margin: f64,
+    /// Allied ships needed per deposit point before another dropoff is built
+    pub ships_per_drop: usize,
+    /// Everything else, the same in every game
+    pub params: Params,
+}
+
+impl Profile {
+    /// Profile for a game between `players` on a `width` by `height` map.
+    ///
+    /// Four-player games are crowded: each new ship crowds the others more
+    /// and enemies are harder to avoid, so we spawn more reluctantly and take
+    /// fewer chances. Larger maps spread halite out, so they need more
+    /// dropoffs.
+    pub fn new(players: usize, width: usize, height: usize, params: Params) -> Self {
+        let area = width * height;
+        let ships_per_drop = if area >= params.large_map * params.large_map {
+            params.ships_per_drop_large
+        } else {
+            params.ships_per_drop_small
+        };
+
+        if players > 2 {
+            Profile {
+                max_risk: params.max_risk_4p,
+                spawn_margin: params.spawn_margin_4p,
+                ships_per_drop,
+                params,
+            }
+        } else {
+            Profile {
+                max_risk: params.max_risk_2p,
+                spawn_margin: params.spawn_margin_2p,
+                ships_per_drop,
+                params,
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::Write;
+
+use constants::Constants;
+use data::{Shipyard, State};
+
+/// Fresh game state for `yards.len()` players on the given map, before
+/// the first turn has been played.
+pub fn setup(
+    constants: &Constants,
+    width: usize,
+    height: usize,
+    yards: Vec<Shipyard>,
+    halite: Vec<usize>,
+) -> State {
+    State {
+        id: 0,
+        width,
+        height,
+        round: 0,
+        scores: vec![constants.INITIAL_ENERGY; yards.len()],
+        drops: Vec::new(),
+        ships: Vec::new(),
+        yards,
+        halite,
+        next_ship: 0,
+    }
+}
+
+/// Lines read by `State::initialize` for the bot playing as `player`.
+pub fn initialization(state: &State, player: usize) -> Vec<String> {
+    let mut lines = Vec::with_capacity(state.height + state.yards.len() + 2);
+    lines.push(format!("{} {}", state.yards.len(), player));
+    for yard in &state.yards {
+        lines.push(format!("{} {} {}", yard.owner, yard.x, yard.y));
+    }
+    lines.push(format!("{} {}", state.width, state.height));
+    for row in state.halite.chunks(state.width) {
+        lines.push(join(row));
+    }
+    lines
+}
+
+/// Lines read by `State::update` when the game moves from `prev` to `next`.
+pub fn update(prev: &State, next: &State) -> Vec<String> {
+    let mut lines = Vec::new();
+    lines.push(next.round.to_string());
+
+    for player in 0..next.scores.len() {
+        let ships = next.ships.iter()
+            .filter(|ship| ship.owner == player)
+            .collect::<Vec<_>>();
+        let drops = next.drops.iter()
+            .enumerate()
+            .filter(|(_, drop)| drop.owner == player)
+            .collect::<Vec<_>>();
+
+        lines.push(format!("{} {} {} {}", player, ships.len(), drops.len(), next.scores[player]));
+        for ship in ships {
+            lines.push(format!("{} {} {} {}", ship.id, ship.x, ship.y, ship.halite));
+        }
+        for (id, drop) in drops {
+            lines.push(format!("{} {} {}", id, drop.x, drop.y));
+        }
+    }
+
+    let changed = prev.halite.iter()
+        .zip(&next.halite)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(index, (_, halite))| (index % next.width, index / next.width, *halite))
+        .collect::<Vec<_>>();
+
+    lines.push(changed.len().to_string());
+    for (x, y, halite) in changed {
+        lines.push(format!("{} {} {}", x, y, halite));
+    }
+    lines
+}
+
+fn join(row: &[usize]) -> String {
+    let mut line = String::with_capacity(row.len() * 4);
+    for (i, halite) in row.iter().enumerate() {
+        if i > 0 { line.push(' '); }
+        let _ = write!(line, "{}", halite);
+    }
+    line
+}
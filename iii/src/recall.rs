@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use constants::Constants;
+use data::State;
+use grid::{DIRS, Dir, Grid, Pos};
+use profile::Profile;
+
+/// IDs of loaded ships that must head home now to deposit before the game
+/// ends.
+///
+/// Ships only crash onto a deposit point on the last turns, so before then it
+/// takes in one ship a turn, and `recall_throughput` on the last. Arrival
+/// turns are handed out backwards from the last round: the farthest ships
+/// get the latest slots. A ship is recalled once its latest departure for
+/// its slot, given its path length home, has come.
+pub fn schedule(constants: &Constants, profile: &Profile, state: &State, grid: &Grid) -> FnvHashSet<usize> {
+
+    let (distances, homes) = paths_home(state, grid);
+
+    let mut queues: FnvHashMap<Pos, Vec<(usize, usize)>> = FnvHashMap::default();
+    for ship in state.allies().filter(|ship| ship.halite > 0) {
+        let index = ship.y * state.width + ship.x;
+        queues.entry(homes[index])
+            .or_default()
+            .push((distances[index], ship.id));
+    }
+
+    let mut recalled = FnvHashSet::default();
+    for (_, mut queue) in queues {
+        queue.sort_by(|a, b| b.cmp(a));
+        for (rank, (distance, id)) in queue.into_iter().enumerate() {
+            let arrival = constants.MAX_TURNS.saturating_sub((rank + 1).saturating_sub(profile.params.recall_throughput));
+            if state.round + distance + profile.params.recall_slack >= arrival {
+                recalled.insert(id);
+            }
+        }
+    }
+    recalled
+}
+
+/// Steps from every cell to the nearest deposit point, and which one it is,
+/// going around enemy ships and allies that can't move.
+fn paths_home(state: &State, grid: &Grid) -> (Vec<usize>, Vec<Pos>) {
+    let (width, height) = (state.width, state.height);
+    let mut distances = vec![usize::MAX; width * height];
+    let mut homes = vec![Pos(0, 0); width * height];
+    let mut queue = VecDeque::new();
+
+    for deposit in grid.deposits() {
+        let index = deposit.1 * width + deposit.0;
+        distances[index] = 0;
+        homes[index] = deposit;
+        queue.push_back(deposit);
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        let index = pos.1 * width + pos.0;
+        let blocked = !grid.is_deposit(pos)
+            && (grid.is_stuck(pos) || grid.enemies_around(pos, 0) > 0);
+        if blocked { continue }
+
+        for dir in DIRS.iter().filter(|dir| **dir != Dir::O) {
+            let next = grid.step(pos, *dir);
+            let next_index = next.1 * width + next.0;
+            if distances[next_index] != usize::MAX { continue }
+            distances[next_index] = distances[index] + 1;
+            homes[next_index] = homes[index];
+            queue.push_back(next);
+        }
+    }
+
+    // Cells walled off entirely fall back to straight-line distance
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            if distances[index] == usize::MAX {
+                let pos = Pos(x, y);
+                homes[index] = grid.nearest_deposit(pos);
+                distances[index] = grid.dist(pos, homes[index]);
+            }
+        }
+    }
+
+    (distances, homes)
+}
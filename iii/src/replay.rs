@@ -0,0 +1,686 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use failure;
+use fnv::FnvHashSet;
+use serde_json;
+use zstd;
+
+use constants::Constants;
+use command::Command;
+use data::{Dropoff, Ship, Shipyard, State};
+use grid::Dir;
+use rules::Rules;
+use simulate;
+
+/// A decoded Halite III replay.
+///
+/// `states[turn]` is the game as every bot saw it at the start of round
+/// `turn + 1`, and `commands[turn][player]` holds what `player` sent in
+/// response.
+/// Each `State` is reported from player 0's perspective.
+///
+/// The layout follows the official engine's replay format as far as we know
+/// it, but has only been checked against replays our own `Recorder` writes:
+/// no replay from the official engine is on hand to test against, so expect
+/// to fix up field names or frame timing the first time one is read.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub constants: Constants,
+    pub names: Vec<String>,
+    pub states: Vec<State>,
+    pub commands: Vec<Vec<Vec<Command>>>,
+}
+
+const ENGINE_VERSION: &str = "my_bot";
+const REPLAY_FILE_VERSION: usize = 3;
+
+#[derive(Serialize, Deserialize)]
+struct RawReplay {
+    #[serde(rename = "ENGINE_VERSION", default)]
+    engine_version: String,
+    #[serde(rename = "REPLAY_FILE_VERSION", default)]
+    replay_file_version: usize,
+    #[serde(rename = "GAME_CONSTANTS")]
+    constants: Constants,
+    #[serde(default)]
+    map_generator_seed: usize,
+    #[serde(default)]
+    number_of_players: usize,
+    players: Vec<RawPlayer>,
+    production_map: RawMap,
+    full_frames: Vec<RawFrame>,
+    #[serde(default)]
+    game_statistics: RawStatistics,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawPlayer {
+    player_id: usize,
+    name: String,
+    energy: usize,
+    factory_location: RawLocation,
+    #[serde(default)]
+    entities: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawMap {
+    width: usize,
+    height: usize,
+    grid: Vec<Vec<RawCell>>,
+    #[serde(default)]
+    map_generator: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawCell {
+    energy: usize,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct RawLocation {
+    x: usize,
+    y: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawFrame {
+    #[serde(default)]
+    cells: Vec<RawChange>,
+    #[serde(default)]
+    deposited: BTreeMap<usize, usize>,
+    #[serde(default)]
+    energy: BTreeMap<usize, usize>,
+    #[serde(default)]
+    entities: BTreeMap<usize, BTreeMap<usize, RawEntity>>,
+    #[serde(default)]
+    events: Vec<RawEvent>,
+    #[serde(default)]
+    moves: BTreeMap<usize, Vec<RawMove>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawChange {
+    x: usize,
+    y: usize,
+    production: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawEntity {
+    x: usize,
+    y: usize,
+    energy: usize,
+    #[serde(default)]
+    is_inspired: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    location: Option<RawLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner_id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    energy: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ships: Option<Vec<usize>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawMove {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    direction: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct RawStatistics {
+    #[serde(default)]
+    number_turns: usize,
+    #[serde(default)]
+    player_statistics: Vec<RawPlayerStatistics>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawPlayerStatistics {
+    player_id: usize,
+    rank: usize,
+    last_turn_alive: usize,
+    final_production: usize,
+    total_production: usize,
+    total_mined: usize,
+    ships_spawned: usize,
+    ships_peak: usize,
+    number_dropoffs: usize,
+    self_collisions: usize,
+    all_collisions: usize,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Decode a replay, accepting both zstd-compressed and plain JSON input.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, failure::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let raw: RawReplay = if bytes.first() == Some(&b'{') {
+            serde_json::from_slice(&bytes)?
+        } else {
+            serde_json::from_reader(zstd::Decoder::new(&bytes[..])?)?
+        };
+        Ok(Self::decode(raw))
+    }
+
+    fn decode(raw: RawReplay) -> Self {
+
+        let RawReplay { constants, mut players, production_map, full_frames, .. } = raw;
+        let (width, height) = (production_map.width, production_map.height);
+        players.sort_by_key(|player| player.player_id);
+
+        let names = players.iter()
+            .map(|player| player.name.clone())
+            .collect();
+
+        let yards = players.iter()
+            .map(|player| Shipyard {
+                owner: player.player_id,
+                x: player.factory_location.x,
+                y: player.factory_location.y,
+            })
+            .collect();
+
+        let halite = production_map.grid.iter()
+            .flat_map(|row| row.iter().map(|cell| cell.energy))
+            .collect();
+
+        let mut state = State {
+            id: 0,
+            width,
+            height,
+            round: 0,
+            scores: players.iter().map(|player| player.energy).collect(),
+            drops: Vec::new(),
+            ships: Vec::new(),
+            yards,
+            halite,
+            next_ship: 0,
+        };
+
+        let mut states = Vec::with_capacity(full_frames.len());
+        let mut commands = Vec::with_capacity(full_frames.len());
+
+        for (turn, frame) in full_frames.into_iter().enumerate() {
+
+            state.round = turn + 1;
+            state.ships = frame.entities.iter()
+                .flat_map(|(owner, ships)| ships.iter().map(move |(id, ship)| Ship {
+                    owner: *owner,
+                    id: *id,
+                    x: ship.x,
+                    y: ship.y,
+                    halite: ship.energy,
+                }))
+                .collect();
+
+            states.push(state.clone());
+
+            let mut turn_commands = vec![Vec::new(); state.scores.len()];
+            for (player, moves) in frame.moves {
+                if player >= turn_commands.len() { continue }
+                turn_commands[player] = moves.iter()
+                    .filter_map(decode_move)
+                    .collect();
+            }
+            commands.push(turn_commands);
+
+            // Apply the outcome of this turn for the next frame
+            for change in frame.cells {
+                state.halite[change.y * width + change.x] = change.production;
+            }
+
+            for (player, energy) in frame.energy {
+                if player < state.scores.len() {
+                    state.scores[player] = energy;
+                }
+            }
+
+            for event in frame.events {
+                if let ("construct", Some(location), Some(owner)) = (event.kind.as_str(), event.location, event.owner_id) {
+                    state.drops.push(Dropoff { owner, x: location.x, y: location.y });
+                }
+            }
+        }
+
+        Replay { constants, names, states, commands }
+    }
+}
+
+fn decode_move(raw: &RawMove) -> Option<Command> {
+    match (raw.kind.as_str(), raw.id) {
+    | ("g", _)        => Some(Command::Spawn),
+    | ("c", Some(id)) => Some(Command::Transform(id)),
+    | ("m", Some(id)) => {
+        let dir = match raw.direction.as_deref() {
+        | Some("n") => Dir::N,
+        | Some("s") => Dir::S,
+        | Some("e") => Dir::E,
+        | Some("w") => Dir::W,
+        | Some("o") => Dir::O,
+        | _         => return None,
+        };
+        Some(Command::Move(id, dir))
+    }
+    | _ => None,
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct Statistics {
+    last_turn_alive: usize,
+    deposited: usize,
+    mined: usize,
+    spawned: usize,
+    peak: usize,
+    self_collisions: usize,
+    all_collisions: usize,
+}
+
+/// Accumulates locally played turns and writes them out in the official
+/// replay format, so they can be opened in the standard visualizer.
+pub struct Recorder {
+    constants: Constants,
+    names: Vec<String>,
+    initial: State,
+    last: State,
+    frames: Vec<RawFrame>,
+    statistics: Vec<Statistics>,
+}
+
+impl Recorder {
+    pub fn new(constants: &Constants, names: Vec<String>, state: &State) -> Self {
+        Recorder {
+            constants: constants.clone(),
+            names,
+            initial: state.clone(),
+            last: state.clone(),
+            frames: Vec::new(),
+            statistics: vec![Statistics::default(); state.scores.len()],
+        }
+    }
+
+    /// Record the turn that took the game from `prev` to `next`, where
+    /// `commands[player]` are the commands `player` issued from `prev`.
+    pub fn record(&mut self, prev: &State, commands: &[Vec<Command>], next: &State) {
+
+        let players = prev.scores.len();
+        let rules = Rules::new(&self.constants);
+        let inspired = simulate::inspiration(&self.constants, prev);
+        let mut events = Vec::new();
+        let mut spent = vec![0; players];
+
+        // Dropoffs constructed this turn
+        let mut transformed = Vec::new();
+        for (id, drop) in next.drops.iter().enumerate().skip(prev.drops.len()) {
+            let cell = prev.halite[drop.y * prev.width + drop.x];
+            if let Some(ship) = prev.ships.iter().find(|ship| ship.x == drop.x && ship.y == drop.y) {
+                spent[drop.owner] += rules.dropoff_cost(ship.halite, cell);
+                transformed.push(ship.id);
+            }
+            events.push(RawEvent {
+                kind: "construct".to_string(),
+                location: Some(RawLocation { x: drop.x, y: drop.y }),
+                owner_id: Some(drop.owner),
+                id: Some(id),
+                energy: None,
+                ships: None,
+            });
+        }
+
+        // Ships spawned this turn
+        for (player, commands) in commands.iter().enumerate() {
+            if commands.contains(&Command::Spawn)
+            && prev.scores[player] >= rules.spawn_cost() + spent[player] {
+                spent[player] += rules.spawn_cost();
+                self.statistics[player].spawned += 1;
+            }
+        }
+
+        for ship in &next.ships {
+            if prev.ships.iter().any(|prev| prev.id == ship.id) { continue }
+            events.push(RawEvent {
+                kind: "spawn".to_string(),
+                location: Some(RawLocation { x: ship.x, y: ship.y }),
+                owner_id: Some(ship.owner),
+                id: Some(ship.id),
+                energy: Some(ship.halite),
+                ships: None,
+            });
+        }
+
+        // Ships destroyed this turn, grouped by where they collided
+        let mut wrecks: BTreeMap<(usize, usize), Vec<&Ship>> = BTreeMap::new();
+        for ship in &prev.ships {
+            if transformed.contains(&ship.id)
+            || next.ships.iter().any(|next| next.id == ship.id) {
+                continue
+            }
+            let dir = commands.get(ship.owner)
+                .and_then(|commands| commands.iter().find_map(|command| match command {
+                    | Command::Move(id, dir) if *id == ship.id => Some(*dir),
+                    | _ => None,
+                }))
+                .unwrap_or(Dir::O);
+            let cell = prev.halite[ship.y * prev.width + ship.x];
+            let cost = rules.move_cost(cell, inspired.contains(&ship.id));
+            let dir = if ship.halite >= cost { dir } else { Dir::O };
+            let location = simulate::shift(prev.width, prev.height, ship.x, ship.y, dir);
+            wrecks.entry(location).or_default().push(ship);
+        }
+
+        for ((x, y), ships) in wrecks {
+            let owner = ships[0].owner;
+            let own = ships.iter().all(|ship| ship.owner == owner);
+            for ship in &ships {
+                self.statistics[ship.owner].all_collisions += 1;
+                if own { self.statistics[ship.owner].self_collisions += 1; }
+            }
+            events.push(RawEvent {
+                kind: "shipwreck".to_string(),
+                location: Some(RawLocation { x, y }),
+                owner_id: None,
+                id: None,
+                energy: None,
+                ships: Some(ships.iter().map(|ship| ship.id).collect()),
+            });
+        }
+
+        // Per-player statistics
+        for (player, spent) in spent.into_iter().enumerate() {
+            let statistics = &mut self.statistics[player];
+            let before = prev.scores[player] as i64 - spent as i64;
+            statistics.deposited += i64::max(0, next.scores[player] as i64 - before) as usize;
+
+            let ships = next.ships.iter()
+                .filter(|ship| ship.owner == player)
+                .count();
+            statistics.peak = usize::max(statistics.peak, ships);
+            if ships > 0 || next.scores[player] >= rules.spawn_cost() {
+                statistics.last_turn_alive = next.round;
+            }
+        }
+
+        for ship in &next.ships {
+            if let Some(prev) = prev.ships.iter().find(|prev| prev.id == ship.id) {
+                if prev.x == ship.x && prev.y == ship.y && ship.halite > prev.halite {
+                    self.statistics[ship.owner].mined += ship.halite - prev.halite;
+                }
+            }
+        }
+
+        let moves = commands.iter()
+            .enumerate()
+            .map(|(player, commands)| (player, commands.iter().map(encode_move).collect()))
+            .collect();
+
+        let mut frame = self.frame(prev, &inspired);
+        frame.moves = moves;
+        frame.events = events;
+        frame.energy = next.scores.iter().cloned().enumerate().collect();
+        frame.cells = prev.halite.iter()
+            .zip(&next.halite)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, (_, production))| RawChange {
+                x: index % prev.width,
+                y: index / prev.width,
+                production: *production,
+            })
+            .collect();
+
+        self.frames.push(frame);
+        self.last = next.clone();
+    }
+
+    /// Entity snapshot of `state`, along with cumulative deposits so far.
+    fn frame(&self, state: &State, inspired: &FnvHashSet<usize>) -> RawFrame {
+        let mut entities = (0..state.scores.len())
+            .map(|player| (player, BTreeMap::new()))
+            .collect::<BTreeMap<_, _>>();
+
+        for ship in &state.ships {
+            entities.entry(ship.owner).or_default().insert(ship.id, RawEntity {
+                x: ship.x,
+                y: ship.y,
+                energy: ship.halite,
+                is_inspired: inspired.contains(&ship.id),
+            });
+        }
+
+        RawFrame {
+            cells: Vec::new(),
+            deposited: self.statistics.iter()
+                .map(|statistics| statistics.deposited)
+                .enumerate()
+                .collect(),
+            energy: state.scores.iter().cloned().enumerate().collect(),
+            entities,
+            events: Vec::new(),
+            moves: BTreeMap::new(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(self, path: P) -> Result<(), failure::Error> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Write the zstd-compressed replay, closing it with a frame for the
+    /// final state.
+    pub fn write<W: Write>(mut self, writer: W) -> Result<(), failure::Error> {
+
+        let inspired = simulate::inspiration(&self.constants, &self.last);
+        let last = self.frame(&self.last, &inspired);
+        self.frames.push(last);
+
+        let players = self.initial.scores.len();
+        let mut ranking = (0..players).collect::<Vec<_>>();
+        ranking.sort_by_key(|player| usize::MAX - self.last.scores[*player]);
+
+        let player_statistics = (0..players)
+            .map(|player| {
+                let statistics = self.statistics[player];
+                RawPlayerStatistics {
+                    player_id: player,
+                    rank: ranking.iter().position(|rank| *rank == player).unwrap_or(0) + 1,
+                    last_turn_alive: statistics.last_turn_alive,
+                    final_production: self.last.scores[player],
+                    total_production: statistics.deposited,
+                    total_mined: statistics.mined,
+                    ships_spawned: statistics.spawned,
+                    ships_peak: statistics.peak,
+                    number_dropoffs: self.last.drops.iter()
+                        .filter(|drop| drop.owner == player)
+                        .count(),
+                    self_collisions: statistics.self_collisions,
+                    all_collisions: statistics.all_collisions,
+                }
+            })
+            .collect();
+
+        let players = self.initial.yards.iter()
+            .map(|yard| RawPlayer {
+                player_id: yard.owner,
+                name: self.names.get(yard.owner).cloned().unwrap_or_default(),
+                energy: self.initial.scores[yard.owner],
+                factory_location: RawLocation { x: yard.x, y: yard.y },
+                entities: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let raw = RawReplay {
+            engine_version: ENGINE_VERSION.to_string(),
+            replay_file_version: REPLAY_FILE_VERSION,
+            map_generator_seed: self.constants.GAME_SEED,
+            number_of_players: players.len(),
+            players,
+            production_map: RawMap {
+                width: self.initial.width,
+                height: self.initial.height,
+                grid: self.initial.halite.chunks(self.initial.width)
+                    .map(|row| row.iter().map(|energy| RawCell { energy: *energy }).collect())
+                    .collect(),
+                map_generator: ENGINE_VERSION.to_string(),
+            },
+            game_statistics: RawStatistics {
+                number_turns: self.frames.len(),
+                player_statistics,
+            },
+            full_frames: self.frames,
+            constants: self.constants,
+        };
+
+        let mut encoder = zstd::Encoder::new(writer, 0)?;
+        serde_json::to_writer(&mut encoder, &raw)?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+fn encode_move(command: &Command) -> RawMove {
+    match command {
+    | Command::Spawn => RawMove {
+        kind: "g".to_string(),
+        id: None,
+        direction: None,
+    },
+    | Command::Transform(id) => RawMove {
+        kind: "c".to_string(),
+        id: Some(*id),
+        direction: None,
+    },
+    | Command::Move(id, dir) => RawMove {
+        kind: "m".to_string(),
+        id: Some(*id),
+        direction: Some(dir.to_string()),
+    },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::*;
+    use harness;
+    use mapgen;
+    use params::Params;
+    use profile::Profile;
+    use protocol;
+    use strategy::Executor;
+
+    #[test]
+    fn recorded_games_read_back_unchanged() {
+        let constants = Constants { GAME_SEED: 3, MAX_TURNS: 150, ..Constants::default() };
+        let (yards, halite) = mapgen::generate(&constants, 2, 32, 32).unwrap();
+        let state = protocol::setup(&constants, 32, 32, yards, halite);
+
+        let executor = || {
+            let profile = Profile::new(2, 32, 32, Params::default());
+            Executor::with_budget(state.halite.iter().sum(), profile, Duration::from_secs(60))
+        };
+        let (mut a, mut b) = (executor(), executor());
+        let history = harness::play(&constants, state, &mut [&mut a, &mut b]);
+
+        let names = vec!["a".to_string(), "b".to_string()];
+        let mut recorder = Recorder::new(&constants, names.clone(), &history.states[0]);
+        for (turn, commands) in history.commands.iter().enumerate() {
+            recorder.record(&history.states[turn], commands, &history.states[turn + 1]);
+        }
+
+        let mut bytes = Vec::new();
+        recorder.write(&mut bytes).unwrap();
+        let replay = Replay::read(&bytes[..]).unwrap();
+
+        assert_eq!(replay.names, names);
+        assert_eq!(replay.states.len(), history.states.len());
+        for (turn, (read, played)) in replay.states.iter().zip(&history.states).enumerate() {
+            let mut ships = played.ships.clone();
+            ships.sort_by_key(|ship| (ship.owner, ship.id));
+            assert_eq!(read.round, played.round, "turn {}", turn);
+            assert_eq!(read.ships, ships, "turn {}", turn);
+            assert_eq!(read.scores, played.scores, "turn {}", turn);
+            assert_eq!(read.halite, played.halite, "turn {}", turn);
+            assert_eq!(read.drops, played.drops, "turn {}", turn);
+        }
+
+        assert!(history.commands.iter().any(|commands| commands.iter().any(|commands| !commands.is_empty())));
+        assert_eq!(&replay.commands[..history.commands.len()], &history.commands[..]);
+        assert!(replay.commands[history.commands.len()..].iter().flatten().all(Vec::is_empty));
+    }
+
+    /// Handwritten in the official layout, with fields we don't read, since
+    /// no replay from the official engine is on hand.
+    const OFFICIAL: &str = r#"{
+        "ENGINE_VERSION": "1.1.6",
+        "REPLAY_FILE_VERSION": 3,
+        "GAME_CONSTANTS": { "MAX_TURNS": 2, "NEW_ENTITY_ENERGY_COST": 1000 },
+        "map_generator_seed": 7,
+        "number_of_players": 2,
+        "players": [
+            { "player_id": 1, "name": "b", "energy": 5000, "entities": [], "factory_location": { "x": 2, "y": 1 } },
+            { "player_id": 0, "name": "a", "energy": 5000, "entities": [], "factory_location": { "x": 1, "y": 1 } }
+        ],
+        "production_map": {
+            "width": 3, "height": 2, "map_generator": "basic",
+            "grid": [
+                [{ "energy": 10 }, { "energy": 20 }, { "energy": 30 }],
+                [{ "energy": 40 }, { "energy": 0 }, { "energy": 0 }]
+            ]
+        },
+        "full_frames": [
+            {
+                "cells": [], "deposited": {}, "energy": { "0": 4000, "1": 5000 },
+                "entities": {}, "events": [],
+                "moves": { "0": [{ "type": "g" }], "1": [] }
+            },
+            {
+                "cells": [{ "x": 0, "y": 0, "production": 8 }], "deposited": { "0": 0, "1": 0 },
+                "energy": { "0": 4000, "1": 5000 },
+                "entities": { "0": { "0": { "x": 1, "y": 1, "energy": 0, "is_inspired": false } } },
+                "events": [{ "type": "spawn", "location": { "x": 1, "y": 1 }, "owner_id": 0, "id": 0, "energy": 0 }],
+                "moves": { "0": [{ "type": "m", "id": 0, "direction": "n" }, { "type": "x" }] }
+            }
+        ],
+        "game_statistics": { "number_turns": 2, "player_statistics": [] }
+    }"#;
+
+    #[test]
+    fn plain_json_in_the_official_layout_is_read() {
+        let replay = Replay::read(OFFICIAL.as_bytes()).unwrap();
+        assert_eq!(replay.constants.MAX_TURNS, 2);
+        assert_eq!(replay.names, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(replay.states.len(), 2);
+        assert_eq!(replay.states[0].yards[1], Shipyard { owner: 1, x: 2, y: 1 });
+        assert_eq!(replay.states[0].halite, vec![10, 20, 30, 40, 0, 0]);
+        assert!(replay.states[0].ships.is_empty());
+        assert_eq!(replay.commands[0], vec![vec![Command::Spawn], vec![]]);
+
+        let state = &replay.states[1];
+        assert_eq!(state.round, 2);
+        assert_eq!(state.scores, vec![4000, 5000]);
+        assert_eq!(state.ships, vec![Ship { owner: 0, id: 0, x: 1, y: 1, halite: 0 }]);
+        assert_eq!(replay.commands[1], vec![vec![Command::Move(0, Dir::N)], vec![]]);
+    }
+}
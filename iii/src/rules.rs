@@ -0,0 +1,86 @@
+use constants::Constants;
+
+/// Game mechanics derived from the engine's `Constants`, so nothing else
+/// has to hard-code the default ratios.
+#[derive(Copy, Clone, Debug)]
+pub struct Rules {
+    move_cost_ratio: usize,
+    inspired_move_cost_ratio: usize,
+    extract_ratio: usize,
+    inspired_extract_ratio: usize,
+    inspired_bonus: f32,
+    capacity: usize,
+    dropoff_cost: usize,
+    spawn_cost: usize,
+}
+
+impl Rules {
+    pub fn new(constants: &Constants) -> Self {
+        Rules {
+            move_cost_ratio: usize::max(constants.MOVE_COST_RATIO, 1),
+            inspired_move_cost_ratio: usize::max(constants.INSPIRED_MOVE_COST_RATIO, 1),
+            extract_ratio: usize::max(constants.EXTRACT_RATIO, 1),
+            inspired_extract_ratio: usize::max(constants.INSPIRED_EXTRACT_RATIO, 1),
+            inspired_bonus: constants.INSPIRED_BONUS_MULTIPLIER,
+            capacity: constants.MAX_ENERGY,
+            dropoff_cost: constants.DROPOFF_COST,
+            spawn_cost: constants.NEW_ENTITY_ENERGY_COST,
+        }
+    }
+
+    /// Halite a ship pays to leave a cell holding `halite`.
+    pub fn move_cost(&self, halite: usize, inspired: bool) -> usize {
+        if inspired {
+            halite / self.inspired_move_cost_ratio
+        } else {
+            halite / self.move_cost_ratio
+        }
+    }
+
+    /// Halite removed from a cell holding `cell`, and halite gained by a ship
+    /// already carrying `cargo`, when the ship mines for one turn.
+    pub fn extract(&self, cell: usize, cargo: usize, inspired: bool) -> (usize, usize) {
+        let ratio = if inspired { self.inspired_extract_ratio } else { self.extract_ratio };
+        let room = self.capacity.saturating_sub(cargo);
+        let mut extracted = cell.div_ceil(ratio);
+        let mut gained = if inspired {
+            extracted + (extracted as f32 * self.inspired_bonus) as usize
+        } else {
+            extracted
+        };
+
+        if gained > room {
+            gained = room;
+            extracted = usize::min(extracted, room);
+        }
+
+        (extracted, gained)
+    }
+
+    /// Fraction of a cell an uninspired ship mines per turn, as `1 / ratio`.
+    pub fn extract_ratio(&self) -> usize {
+        self.extract_ratio
+    }
+
+    /// Most halite a ship can carry.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Halite a player pays to convert a ship carrying `cargo` on a cell
+    /// holding `cell` into a dropoff.
+    pub fn dropoff_cost(&self, cargo: usize, cell: usize) -> usize {
+        self.dropoff_cost.saturating_sub(cargo + cell)
+    }
+
+    /// Halite a player pays to spawn a ship.
+    pub fn spawn_cost(&self) -> usize {
+        self.spawn_cost
+    }
+
+    /// Halite treated as equivalent to one turn of travel: a full ship's
+    /// move cost off a full cell.
+    pub fn halite_per_turn(&self) -> usize {
+        usize::max(self.capacity / self.move_cost_ratio, 1)
+    }
+}
@@ -0,0 +1,353 @@
+use fnv::{FnvHashMap, FnvHashSet};
+
+use constants::Constants;
+use command::Command;
+use data::{Dropoff, Ship, State};
+use grid::Dir;
+use rules::Rules;
+
+/// Advance `state` by a single turn under the Halite III rules.
+///
+/// `commands[player]` holds the commands issued by `player` this turn.
+/// Commands for ships the player doesn't own, or for ships that no longer
+/// exist, are ignored, as is any ship that was given more than one command.
+pub fn step(constants: &Constants, state: &State, commands: &[Vec<Command>]) -> State {
+
+    let (width, height) = (state.width, state.height);
+    let rules = Rules::new(constants);
+    let mut next = state.clone();
+    next.round += 1;
+
+    let inspired = inspiration(constants, state);
+    let mut spawn = vec![false; state.scores.len()];
+    let mut transform = FnvHashSet::default();
+    let mut moves = FnvHashMap::default();
+    let mut issued = FnvHashSet::default();
+    let mut invalid = FnvHashSet::default();
+
+    for (player, commands) in commands.iter().enumerate() {
+        for command in commands {
+            let id = match command {
+            | Command::Spawn => { spawn[player] = true; continue }
+            | Command::Transform(id)
+            | Command::Move(id, _) => *id,
+            };
+
+            if !state.ships.iter().any(|ship| ship.id == id && ship.owner == player) {
+                continue
+            }
+
+            if !issued.insert(id) {
+                invalid.insert(id);
+                continue
+            }
+
+            match command {
+            | Command::Transform(_) => { transform.insert(id); }
+            | Command::Move(_, dir) => { moves.insert(id, *dir); }
+            | Command::Spawn        => unreachable!(),
+            }
+        }
+    }
+
+    // Dropoff construction
+    let mut ships = Vec::with_capacity(state.ships.len());
+    for ship in &state.ships {
+        let index = ship.y * width + ship.x;
+        if transform.contains(&ship.id)
+        && !invalid.contains(&ship.id)
+        && structure(&next, ship.x, ship.y).is_none() {
+            let cost = rules.dropoff_cost(ship.halite, next.halite[index]);
+            if next.scores[ship.owner] >= cost {
+                next.scores[ship.owner] -= cost;
+                next.halite[index] = 0;
+                next.drops.push(Dropoff { owner: ship.owner, x: ship.x, y: ship.y });
+                continue
+            }
+        }
+        ships.push(*ship);
+    }
+
+    // Movement
+    let mut still = FnvHashSet::default();
+    for ship in &mut ships {
+        let dir = if invalid.contains(&ship.id) {
+            Dir::O
+        } else {
+            moves.get(&ship.id).cloned().unwrap_or(Dir::O)
+        };
+
+        let index = ship.y * width + ship.x;
+        let cost = rules.move_cost(next.halite[index], inspired.contains(&ship.id));
+
+        if dir == Dir::O || ship.halite < cost {
+            still.insert(ship.id);
+            continue
+        }
+
+        ship.halite -= cost;
+        let (x, y) = shift(width, height, ship.x, ship.y, dir);
+        ship.x = x;
+        ship.y = y;
+    }
+
+    // Spawning, never handing out the ID of a ship that has since been
+    // destroyed
+    let mut id = state.ships.iter()
+        .map(|ship| ship.id + 1)
+        .fold(state.next_ship, usize::max);
+
+    for (player, _) in spawn.iter().enumerate().filter(|(_, spawn)| **spawn) {
+        if next.scores[player] < rules.spawn_cost() { continue }
+        let yard = next.yards[player];
+        next.scores[player] -= rules.spawn_cost();
+        ships.push(Ship { owner: player, id, x: yard.x, y: yard.y, halite: 0 });
+        id += 1;
+    }
+    next.next_ship = id;
+
+    // Collisions
+    let mut occupied = FnvHashMap::default();
+    for ship in &ships {
+        *occupied.entry((ship.x, ship.y)).or_insert(0) += 1;
+    }
+
+    let mut survivors = Vec::with_capacity(ships.len());
+    for ship in ships {
+        if occupied[&(ship.x, ship.y)] == 1 {
+            survivors.push(ship);
+            continue
+        }
+        match structure(&next, ship.x, ship.y) {
+        | Some(owner) => next.scores[owner] += ship.halite,
+        | None        => next.halite[ship.y * width + ship.x] += ship.halite,
+        }
+    }
+
+    // Extraction
+    for ship in &mut survivors {
+        if !still.contains(&ship.id) { continue }
+        let index = ship.y * width + ship.x;
+        let (extracted, gained) = rules.extract(
+            next.halite[index],
+            ship.halite,
+            inspired.contains(&ship.id),
+        );
+        next.halite[index] -= extracted;
+        ship.halite += gained;
+    }
+
+    // Deposits
+    for ship in &mut survivors {
+        if structure(&next, ship.x, ship.y) == Some(ship.owner) {
+            next.scores[ship.owner] += ship.halite;
+            ship.halite = 0;
+        }
+    }
+
+    // Captures
+    if constants.CAPTURE_ENABLED {
+        let owners = survivors.iter()
+            .map(|ship| captor(constants, &next, &survivors, ship))
+            .collect::<Vec<_>>();
+        for (ship, owner) in survivors.iter_mut().zip(owners) {
+            ship.owner = owner;
+        }
+    }
+
+    next.ships = survivors;
+    next
+}
+
+/// Owner of `ship` after the capture rule is applied: the player with the
+/// most ships within `CAPTURE_RADIUS`, if it outnumbers the current owner
+/// there by at least `SHIPS_ABOVE_FOR_CAPTURE`.
+fn captor(constants: &Constants, state: &State, ships: &[Ship], ship: &Ship) -> usize {
+    let mut counts = vec![0; state.scores.len()];
+    for other in ships {
+        if distance(state.width, state.height, (ship.x, ship.y), (other.x, other.y)) <= constants.CAPTURE_RADIUS {
+            counts[other.owner] += 1;
+        }
+    }
+
+    let (best, most) = counts.iter()
+        .cloned()
+        .enumerate()
+        .filter(|(player, _)| *player != ship.owner)
+        .max_by_key(|(player, count)| (*count, usize::MAX - player))
+        .unwrap_or((ship.owner, 0));
+
+    if most >= counts[ship.owner] + constants.SHIPS_ABOVE_FOR_CAPTURE {
+        best
+    } else {
+        ship.owner
+    }
+}
+
+/// IDs of ships with at least `INSPIRATION_SHIP_COUNT` enemies within
+/// `INSPIRATION_RADIUS`.
+pub fn inspiration(constants: &Constants, state: &State) -> FnvHashSet<usize> {
+    let mut inspired = FnvHashSet::default();
+    if !constants.INSPIRATION_ENABLED { return inspired }
+    for ship in &state.ships {
+        let enemies = state.ships.iter()
+            .filter(|other| other.owner != ship.owner)
+            .filter(|other| {
+                distance(state.width, state.height, (ship.x, ship.y), (other.x, other.y))
+                    <= constants.INSPIRATION_RADIUS
+            })
+            .count();
+        if enemies >= constants.INSPIRATION_SHIP_COUNT {
+            inspired.insert(ship.id);
+        }
+    }
+    inspired
+}
+
+/// Owner of the shipyard or dropoff at `(x, y)`, if any.
+fn structure(state: &State, x: usize, y: usize) -> Option<usize> {
+    state.yards.iter()
+        .map(|yard| (yard.owner, yard.x, yard.y))
+        .chain(state.drops.iter().map(|drop| (drop.owner, drop.x, drop.y)))
+        .find(|(_, sx, sy)| *sx == x && *sy == y)
+        .map(|(owner, _, _)| owner)
+}
+
+/// Cell reached from `(x, y)` by moving in `dir` on a wrapping map.
+pub fn shift(width: usize, height: usize, x: usize, y: usize, dir: Dir) -> (usize, usize) {
+    match dir {
+    | Dir::N => (x, (y + height - 1) % height),
+    | Dir::S => (x, (y + 1) % height),
+    | Dir::E => ((x + 1) % width, y),
+    | Dir::W => ((x + width - 1) % width, y),
+    | Dir::O => (x, y),
+    }
+}
+
+/// Manhattan distance between `a` and `b` on a wrapping map.
+pub fn distance(width: usize, height: usize, a: (usize, usize), b: (usize, usize)) -> usize {
+    let dx = a.0.abs_diff(b.0);
+    let dy = a.1.abs_diff(b.1);
+    usize::min(dx, width - dx) + usize::min(dy, height - dy)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use data::Shipyard;
+    use protocol;
+
+    /// Two players on an 8 by 8 map with 100 halite on every cell.
+    fn state(ships: &[(usize, usize, usize, usize, usize)]) -> State {
+        let constants = Constants::default();
+        let yards = vec![
+            Shipyard { owner: 0, x: 0, y: 0 },
+            Shipyard { owner: 1, x: 4, y: 4 },
+        ];
+        let mut state = protocol::setup(&constants, 8, 8, yards, vec![100; 64]);
+        state.ships = ships.iter()
+            .map(|&(owner, id, x, y, halite)| Ship { owner, id, x, y, halite })
+            .collect();
+        state
+    }
+
+    fn ship(state: &State, id: usize) -> Option<Ship> {
+        state.ships.iter().find(|ship| ship.id == id).cloned()
+    }
+
+    #[test]
+    fn moving_pays_a_tenth_of_the_cell() {
+        let state = state(&[(0, 0, 2, 2, 50)]);
+        let next = step(&Constants::default(), &state, &[vec![Command::Move(0, Dir::E)], vec![]]);
+        assert_eq!(ship(&next, 0), Some(Ship { owner: 0, id: 0, x: 3, y: 2, halite: 40 }));
+    }
+
+    #[test]
+    fn ships_that_cannot_pay_stay_and_mine() {
+        let state = state(&[(0, 0, 2, 2, 5)]);
+        let next = step(&Constants::default(), &state, &[vec![Command::Move(0, Dir::E)], vec![]]);
+        assert_eq!(ship(&next, 0), Some(Ship { owner: 0, id: 0, x: 2, y: 2, halite: 30 }));
+        assert_eq!(next.halite[2 * 8 + 2], 75);
+    }
+
+    #[test]
+    fn collisions_destroy_both_ships_and_spill_their_cargo() {
+        let state = state(&[(0, 0, 2, 2, 100), (1, 1, 4, 2, 200)]);
+        let commands = [vec![Command::Move(0, Dir::E)], vec![Command::Move(1, Dir::W)]];
+        let next = step(&Constants::default(), &state, &commands);
+        assert!(next.ships.is_empty());
+        assert_eq!(next.halite[2 * 8 + 3], 100 + 90 + 190);
+    }
+
+    #[test]
+    fn collisions_on_a_structure_go_to_its_owner() {
+        let state = state(&[(0, 0, 1, 0, 100), (0, 1, 0, 1, 200)]);
+        let commands = [vec![Command::Move(0, Dir::W), Command::Move(1, Dir::N)], vec![]];
+        let next = step(&Constants::default(), &state, &commands);
+        assert!(next.ships.is_empty());
+        assert_eq!(next.scores[0], 5000 + 90 + 190);
+    }
+
+    #[test]
+    fn ships_deposit_on_their_own_yard() {
+        let state = state(&[(0, 0, 1, 0, 500)]);
+        let next = step(&Constants::default(), &state, &[vec![Command::Move(0, Dir::W)], vec![]]);
+        assert_eq!(next.scores[0], 5000 + 490);
+        assert_eq!(ship(&next, 0).map(|ship| ship.halite), Some(0));
+    }
+
+    #[test]
+    fn inspired_ships_mine_a_bonus() {
+        let state = state(&[(0, 0, 2, 2, 0), (1, 1, 3, 3, 0), (1, 2, 5, 2, 0)]);
+        let next = step(&Constants::default(), &state, &[vec![], vec![]]);
+        assert_eq!(ship(&next, 0).map(|ship| ship.halite), Some(25 + 50));
+        assert_eq!(next.halite[2 * 8 + 2], 75);
+    }
+
+    #[test]
+    fn conversion_costs_dropoff_less_cargo_and_cell() {
+        let state = state(&[(0, 0, 2, 2, 900)]);
+        let next = step(&Constants::default(), &state, &[vec![Command::Transform(0)], vec![]]);
+        assert!(next.ships.is_empty());
+        assert_eq!(next.scores[0], 5000 - (4000 - 900 - 100));
+        assert_eq!(next.halite[2 * 8 + 2], 0);
+        assert_eq!(next.drops, vec![Dropoff { owner: 0, x: 2, y: 2 }]);
+    }
+
+    #[test]
+    fn conversion_needs_enough_halite() {
+        let mut state = state(&[(0, 0, 2, 2, 0)]);
+        state.scores[0] = 1000;
+        let next = step(&Constants::default(), &state, &[vec![Command::Transform(0)], vec![]]);
+        assert!(next.drops.is_empty());
+        assert!(ship(&next, 0).is_some());
+    }
+
+    #[test]
+    fn spawned_ships_never_reuse_ids() {
+        let state = state(&[(0, 0, 2, 2, 0), (0, 5, 3, 2, 0), (1, 6, 4, 2, 50)]);
+        let next = step(&Constants::default(), &state, &[vec![], vec![Command::Move(6, Dir::W)]]);
+        assert!(next.ships.iter().all(|ship| ship.id == 0));
+
+        let next = step(&Constants::default(), &next, &[vec![Command::Spawn], vec![]]);
+        assert_eq!(next.ships.iter().map(|ship| ship.id).max(), Some(7));
+    }
+
+    #[test]
+    fn outnumbered_ships_are_captured() {
+        let constants = Constants { CAPTURE_ENABLED: true, ..Constants::default() };
+        let state = state(&[(1, 0, 2, 2, 100), (0, 1, 1, 2, 0), (0, 2, 3, 2, 0), (0, 3, 2, 1, 0), (0, 4, 2, 4, 0)]);
+        let next = step(&constants, &state, &[vec![], vec![]]);
+        assert_eq!(ship(&next, 0).map(|ship| ship.owner), Some(0));
+        assert!(next.ships.iter().all(|ship| ship.owner == 0));
+    }
+
+    #[test]
+    fn ships_within_the_margin_are_not_captured() {
+        let constants = Constants { CAPTURE_ENABLED: true, ..Constants::default() };
+        let state = state(&[(1, 0, 2, 2, 100), (0, 1, 1, 2, 0), (0, 2, 3, 2, 0), (0, 3, 2, 1, 0)]);
+        let next = step(&constants, &state, &[vec![], vec![]]);
+        assert_eq!(ship(&next, 0).map(|ship| ship.owner), Some(1));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use constants::Constants;
+use data::State;
+use params::Params;
+use rules::Rules;
+
+/// Tracks income per ship to estimate what one more ship would bring in.
+#[derive(Debug, Clone)]
+pub struct Spawner {
+    total: usize,
+    params: Params,
+    /// Halite deposited and ships we had for each of the last `spawn_window` turns
+    history: VecDeque<(usize, usize)>,
+    /// Our score, halite spent since, and fleet size as of last turn
+    last: Option<(usize, usize, usize)>,
+}
+
+impl Spawner {
+    /// `total` is the halite on the map at the start of the game.
+    pub fn new(total: usize, params: Params) -> Self {
+        Spawner {
+            total,
+            params,
+            history: VecDeque::with_capacity(params.spawn_window),
+            last: None,
+        }
+    }
+
+    /// Record how much we deposited since last turn.
+    pub fn observe(&mut self, state: &State) {
+        if let Some((score, spent, ships)) = self.last {
+            let deposited = (state.halite() + spent).saturating_sub(score);
+            if self.history.len() >= self.params.spawn_window {
+                self.history.pop_front();
+            }
+            self.history.push_back((deposited, ships));
+        }
+        self.last = Some((state.halite(), 0, state.allies().count()));
+    }
+
+    /// Record halite spent this turn, so it isn't mistaken for lost income.
+    pub fn spend(&mut self, amount: usize) {
+        if let Some((_, spent, _)) = &mut self.last {
+            *spent += amount;
+        }
+    }
+
+    /// Halite we expect one more ship to deposit over the rest of the game.
+    ///
+    /// A ship brings in about what our ships have been bringing in lately
+    /// for every turn it has to work, but no fleet can take more than its
+    /// share of the halite left on the map.
+    pub fn value(&self, constants: &Constants, rules: &Rules, state: &State) -> f64 {
+        let remaining = state.halite.iter().sum::<usize>() as f64;
+        let scarcity = remaining / usize::max(self.total, 1) as f64;
+        let prior = rules.capacity() as f64 / self.params.spawn_prior_trip * scarcity;
+
+        // Assumed income keeps early rounds with no deposits yet from
+        // reading as zero
+        let deposited = self.history.iter().map(|(deposited, _)| *deposited).sum::<usize>() as f64;
+        let turns = self.history.iter().map(|(_, ships)| *ships).sum::<usize>() as f64;
+        let rate = (deposited + prior * self.params.spawn_prior_weight) / (turns + self.params.spawn_prior_weight);
+
+        let left = constants.MAX_TURNS.saturating_sub(state.round);
+        let working = left.saturating_sub(2 * self.params.spawn_ramp) as f64;
+        let share = remaining / (state.ships.len() + 1) as f64;
+
+        f64::min(rate * working, share)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use data::Ship;
+    use grid::{fixture, Pos};
+
+    /// Us on a 16 by 16 map with `halite` on every cell, a score of `score`
+    /// and `ships` ships.
+    fn state(halite: usize, score: usize, ships: usize) -> State {
+        let mut state = fixture::state(16, halite, [Pos(2, 2), Pos(12, 12)]);
+        state.scores[0] = score;
+        state.ships = (0..ships)
+            .map(|id| Ship { owner: 0, id, x: id, y: 0, halite: 0 })
+            .collect();
+        state
+    }
+
+    #[test]
+    fn spending_is_not_mistaken_for_lost_income() {
+        let mut spawner = Spawner::new(16 * 16 * 200, Params::default());
+        spawner.observe(&state(200, 5000, 2));
+        assert!(spawner.history.is_empty());
+
+        // Built a ship for 1000, and deposited 300
+        spawner.spend(1000);
+        spawner.observe(&state(200, 4300, 3));
+        assert_eq!(spawner.history, vec![(300, 2)]);
+
+        // Spending only counts towards the turn it happened on
+        spawner.observe(&state(200, 4300, 3));
+        assert_eq!(spawner.history, vec![(300, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn history_is_limited_to_the_window() {
+        let params = Params { spawn_window: 2, ..Params::default() };
+        let mut spawner = Spawner::new(16 * 16 * 200, params);
+        for (turn, score) in [0, 100, 300, 600].iter().enumerate() {
+            spawner.observe(&state(200, *score, turn));
+        }
+        assert_eq!(spawner.history, vec![(200, 1), (300, 2)]);
+    }
+
+    #[test]
+    fn ships_are_worth_more_on_a_rich_map() {
+        let constants = Constants::default();
+        let rules = Rules::new(&constants);
+        let spawner = Spawner::new(16 * 16 * 200, Params::default());
+        let rich = spawner.value(&constants, &rules, &state(200, 5000, 4));
+        let depleted = spawner.value(&constants, &rules, &state(20, 5000, 4));
+        assert!(rich > depleted, "{} on a rich map against {} on a depleted one", rich, depleted);
+        assert!(depleted > 0.0);
+    }
+}
@@ -1,60 +1,132 @@
-use fnv::FnvHashSet;
-use hungarian::minimize;
+use std::mem;
+use std::time::{Duration, Instant};
 
+use fixedbitset::FixedBitSet;
+use fnv::{FnvHashMap, FnvHashSet};
+
+use attack;
 use constants::Constants;
 use command::Command;
 use data::State;
-use grid::{Pos, Grid};
+use dropoff;
+use grid::{DIRS, Pos, Grid};
+use matching::{self, Candidate};
+use mining::Policy;
+use profile::Profile;
+use recall;
+use spawn::Spawner;
 
 #[derive(Debug, Clone)]
 pub struct Executor {
-    total: usize,
+    spawner: Spawner,
+    profile: Profile,
     crashing: FnvHashSet<usize>,
     returning: FnvHashSet<usize>,
+    building: Option<(usize, Pos)>,
+    paths: FnvHashMap<usize, Vec<Pos>>,
+    budget: Duration,
+    /// Seconds per unit of assignment work (ships × candidates) last time we ran it
+    rate: f64,
 }
 
 impl Executor {
 
-    pub fn new(total: usize) -> Self {
+    pub fn new(total: usize, profile: Profile) -> Self {
+        Executor::with_budget(total, profile, Duration::from_millis(profile.params.budget))
+    }
+
+    /// An executor allowed `budget` per turn. A budget no turn comes close to
+    /// never falls back to the cheaper plans, so play is deterministic.
+    pub fn with_budget(total: usize, profile: Profile, budget: Duration) -> Self {
         Executor {
-            total,
+            spawner: Spawner::new(total, profile.params),
+            profile,
             crashing: FnvHashSet::default(),
             returning: FnvHashSet::default(),
+            building: None,
+            paths: FnvHashMap::default(),
+            budget,
+            rate: 0.0,
         }
     }
 
     pub fn execute(&mut self, constants: &Constants, state: &State) -> Vec<Command> {
 
-        let mut grid = Grid::new(
-            state.id,
-            state.width,
-            state.height,
-            state.round,
-            &state.halite,
-            &state.ships,
-            &state.drops,
-            &state.yards,
-        );
+        let start = Instant::now();
+        let deadline = start + self.budget * self.profile.params.plan_share / 100;
+
+        let alive = state.allies().map(|ship| ship.id).collect::<FnvHashSet<_>>();
+        self.paths.retain(|id, _| alive.contains(id));
+
+        let profile = self.profile;
+        let paths = mem::take(&mut self.paths);
+        let mut grid = Grid::new(constants, &profile, state, deadline, paths);
+        let rules = *grid.rules();
+        let policy = Policy::new(&grid, &profile, state.width, state.height, state.allies().count());
 
         info!("{}", state.round);
 
-        let yard = state.yards[state.id];
-        let remaining = state.halite.iter().sum::<usize>();
+        self.spawner.observe(state);
 
         let mut allies = state.allies().collect::<Vec<_>>();
-        allies.sort_by_key(|ship| constants.MAX_ENERGY - ship.halite);
+        allies.sort_by_key(|ship| rules.capacity() - ship.halite);
 
+        let mut commands = Vec::new();
         let mut incoming = Vec::new();
         let mut outgoing = Vec::new();
 
+        // Recalled ships head home until they've deposited
+        self.crashing.retain(|id| allies.iter().any(|ship| ship.id == *id && ship.halite > 0));
+        self.crashing.extend(recall::schedule(constants, &profile, state, &grid));
+
+        if self.building.is_none() {
+            self.building = dropoff::plan(constants, &profile, state, &grid).and_then(|site| {
+                allies.iter()
+                    .filter(|ship| !self.crashing.contains(&ship.id))
+                    .min_by_key(|ship| grid.dist(Pos(ship.x, ship.y), site))
+                    .map(|ship| (ship.id, site))
+            });
+        }
+
+        // Drop the build plan if its ship was destroyed, has converted or
+        // been recalled, or the site is no longer worth it; the ship goes
+        // back to normal assignment
+        let crashing = &self.crashing;
+        self.building = self.building.filter(|(id, site)| {
+            allies.iter()
+                .find(|ship| ship.id == *id)
+                .filter(|_| !crashing.contains(id))
+                .is_some_and(|ship| dropoff::viable(constants, &profile, state, &grid, *site, ship.halite))
+        });
+
+        // Halite held back for the planned dropoff
+        let mut reserved = 0;
+
         for ship in &allies {
-            if grid.distance_from_yard(ship) + state.round + 10 >= constants.MAX_TURNS {
-                self.crashing.insert(ship.id);
+            if let Some((id, site)) = self.building {
+                if id == ship.id {
+                    let pos = Pos(ship.x, ship.y);
+                    let cost = rules.dropoff_cost(ship.halite, state.halite[ship.y * state.width + ship.x]);
+                    self.returning.remove(&ship.id);
+                    if pos == site && state.halite() >= cost {
+                        info!("[{}]: building dropoff at {:?}", ship.id, site);
+                        commands.push(Command::Transform(ship.id));
+                        self.spawner.spend(cost);
+                        reserved = cost;
+                    } else {
+                        grid.plan_route(ship, site, false);
+                        reserved = rules.dropoff_cost(ship.halite, 0);
+                    }
+                    continue
+                }
+            }
+
+            if self.crashing.contains(&ship.id) {
                 incoming.push(ship);
-            } else if ship.x == yard.x && ship.y == yard.y {
+            } else if grid.is_deposit(Pos(ship.x, ship.y)) {
                 self.returning.remove(&ship.id);
                 outgoing.push(ship);
-            } else if ship.halite >= 1000 {
+            } else if policy.should_return(&grid, ship) {
                 self.returning.insert(ship.id);
                 incoming.push(ship);
             } else if self.returning.contains(&ship.id) {
@@ -64,61 +136,127 @@ impl Executor {
             }
         }
 
-        let mut costs = Vec::with_capacity(outgoing.len() * state.width * state.height);
-        for ship in &outgoing {
-            grid.fill_cost(&mut costs, |grid, pos, halite| {
-                let cost = (constants.MAX_CELL_PRODUCTION - usize::min(halite, constants.MAX_CELL_PRODUCTION)) / 200
-                         + grid.dist(pos, Pos(yard.x, yard.y))
-                         + grid.dist(Pos(ship.x, ship.y), pos);
-
-                if pos == Pos(yard.x, yard.y) {
-                    usize::max_value()
-                } else if halite >= 100 && !grid.is_stuck(pos) && !(grid.enemies_around(pos, 2) > 0)  {
-                    cost
-                } else if halite >= 12 && halite < 100 {
-                    cost + 100000
-                } else {
-                    usize::max_value()
+        // Close in on enemy ships that are one ally short of being captured,
+        // with a ship that isn't already counted against them
+        for target in grid.capture_targets(state).collect::<Vec<_>>() {
+            let nearest = outgoing.iter()
+                .enumerate()
+                .map(|(i, ship)| (grid.dist(Pos(ship.x, ship.y), target), i))
+                .filter(|(distance, _)| (constants.CAPTURE_RADIUS + 1..=2 * constants.CAPTURE_RADIUS).contains(distance))
+                .min();
+
+            if let Some((_, i)) = nearest {
+                let ship = outgoing.remove(i);
+                let pos = Pos(ship.x, ship.y);
+                let end = DIRS.iter()
+                    .map(|dir| grid.step(target, *dir))
+                    .filter(|end| *end != target)
+                    .min_by_key(|end| grid.dist(pos, *end))
+                    .unwrap_or(pos);
+                info!("[{}]: moving to capture {:?}", ship.id, target);
+                grid.plan_route(ship, end, false);
+            }
+        }
+
+        // Ram loaded enemy ships where the spilled halite is worth our ship
+        if profile.params.attack {
+            let available = outgoing.iter().map(|ship| **ship).collect::<Vec<_>>();
+            for (id, target) in attack::plan(constants, &profile, state, &grid, &available) {
+                if let Some(i) = outgoing.iter().position(|ship| ship.id == id) {
+                    let ship = outgoing.remove(i);
+                    info!("[{}]: attacking {:?}", ship.id, target);
+                    grid.plan_ram(ship, target);
                 }
-            });
+            }
         }
 
-        let assignment = minimize(&costs, outgoing.len(), state.width * state.height)
-            .into_iter()
-            .enumerate();
+        let cells = state.width * state.height;
+        let k = usize::min(outgoing.len(), profile.params.candidates);
+        let mut costs = Vec::with_capacity(cells);
+        let mut candidates = Vec::with_capacity(outgoing.len());
+        for ship in &outgoing {
+            costs.clear();
+            grid.fill(&mut costs, |grid, pos, _| policy.cost(grid, ship, pos));
+            candidates.push(matching::candidates(&costs, k));
+        }
+
+        // Fall back to greedy targets if the optimal assignment won't finish in time
+        let edges = candidates.iter().map(Vec::len).sum::<usize>();
+        let work = (outgoing.len() * edges) as f64;
+        let estimate = Duration::from_secs_f64(self.rate * work);
+        let assignment = if start.elapsed() + estimate < self.budget * profile.params.assign_share / 100 {
+            let before = Instant::now();
+            let assignment = matching::minimize(&candidates);
+            if work > 0.0 {
+                self.rate = before.elapsed().as_secs_f64() / work;
+            }
+            assignment
+        } else {
+            warn!("assigning {} ships greedily", outgoing.len());
+            greedy(&candidates, cells)
+        };
 
-        for (id, dest) in assignment {
-            if let Some(dest) = dest {
-                let ship = outgoing[id];
-                let dest = Pos(dest % state.width, dest / state.width);
-                grid.plan_route(ship, dest, false);
+        // Ships left without a target still need a route, or allies could
+        // be pushed onto them
+        for (ship, dest) in outgoing.iter().zip(assignment) {
+            match dest {
+            | Some(dest) => grid.plan_route(ship, Pos(dest % state.width, dest / state.width), false),
+            | None => grid.plan_stay(ship),
             }
         }
 
         for ship in incoming {
             if self.crashing.contains(&ship.id) {
-                grid.plan_route(ship, Pos(yard.x, yard.y), true);
+                // Piling onto a deposit point destroys our own ships, so it's
+                // only worth it once there's no time left to take turns
+                let pos = Pos(ship.x, ship.y);
+                let arrival = state.round + grid.dist(pos, grid.nearest_deposit(pos));
+                grid.plan_return(ship, arrival + 1 >= constants.MAX_TURNS);
             } else if self.returning.contains(&ship.id) {
-                let crowd = grid.allies_around(Pos(yard.x, yard.y), 1);
-                let distance = grid.distance_from_yard(ship);
+                let pos = Pos(ship.x, ship.y);
+                let deposit = grid.nearest_deposit(pos);
+                let crowd = grid.allies_around(deposit, profile.params.crowd_radius);
+                let distance = grid.dist(pos, deposit);
+                let wait = profile.params.crowd_wait_min..=profile.params.crowd_wait_max;
 
-                if crowd >= 6 && distance <= 5 && distance >= 2 {
-                    grid.plan_route(ship, Pos(ship.x, ship.y), false);
+                if crowd >= profile.params.crowd_limit && wait.contains(&distance) {
+                    grid.plan_route(ship, pos, false);
                 } else {
-                    grid.plan_route(ship, Pos(yard.x, yard.y), false);
+                    grid.plan_return(ship, false);
                 }
             }
         }
 
-        let (spawnable, mut commands) = grid.resolve_routes();
+        if grid.is_expired() {
+            warn!("out of time after {:?}", start.elapsed());
+        }
 
-        if state.halite() >= constants.NEW_ENTITY_ENERGY_COST
-        && remaining >= self.total / 2
-        && state.round <= constants.MAX_TURNS / 2
+        let (spawnable, moves) = grid.resolve_routes();
+        commands.extend(moves);
+        self.paths = grid.take_paths();
+
+        if state.halite() >= rules.spawn_cost() + reserved
+        && self.spawner.value(constants, &rules, state) >= rules.spawn_cost() as f64 * profile.spawn_margin
         && spawnable {
             commands.push(Command::Spawn);
+            self.spawner.spend(rules.spawn_cost());
         }
 
         commands
     }
 }
+
+/// Give each ship in turn its cheapest candidate cell that isn't already taken.
+fn greedy(candidates: &[Vec<Candidate>], cells: usize) -> Vec<Option<usize>> {
+    let mut taken = FixedBitSet::with_capacity(cells);
+    candidates.iter()
+        .map(|row| {
+            let cell = row.iter()
+                .filter(|(cell, _)| !taken[*cell])
+                .min_by_key(|(cell, cost)| (*cost, *cell))
+                .map(|(cell, _)| *cell);
+            if let Some(cell) = cell { taken.put(cell); }
+            cell
+        })
+        .collect()
+}
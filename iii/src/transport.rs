@@ -0,0 +1,122 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Stdin, Stdout, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use failure;
+
+/// Line-based connection between the bot and the engine.
+pub trait Transport {
+    /// Next line from the engine, or `None` once the connection is closed.
+    fn receive(&mut self) -> Option<String>;
+
+    /// Send `line` to the engine right away.
+    fn send(&mut self, line: &str) -> Result<(), failure::Error>;
+}
+
+/// Transport over any reader and writer, such as stdio or a socket.
+pub struct Stream<R, W> {
+    lines: Lines<R>,
+    writer: W,
+}
+
+impl<R: BufRead, W: Write> Stream<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Stream { lines: reader.lines(), writer }
+    }
+}
+
+impl Stream<BufReader<Stdin>, BufWriter<Stdout>> {
+    /// The engine's standard setup: frames on stdin, commands on stdout.
+    pub fn stdio() -> Self {
+        Stream::new(BufReader::new(io::stdin()), BufWriter::new(io::stdout()))
+    }
+}
+
+impl Stream<BufReader<TcpStream>, BufWriter<TcpStream>> {
+    /// Connect to an engine listening at `address`.
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<Self, failure::Error> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Stream::new(reader, BufWriter::new(stream)))
+    }
+}
+
+impl<R: BufRead, W: Write> Transport for Stream<R, W> {
+    fn receive(&mut self) -> Option<String> {
+        self.lines.next().and_then(Result::ok)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), failure::Error> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Transport that stops waiting for a line after a timeout, for talking to
+/// bots that may hang. Lines are read on a background thread.
+///
+/// The first line, which answers initialization, may take `init`; every
+/// later one may take `turn`.
+pub struct Timed<W> {
+    lines: mpsc::Receiver<String>,
+    writer: W,
+    timeout: Duration,
+    turn: Duration,
+}
+
+impl<W: Write> Timed<W> {
+    pub fn new<R: BufRead + Send + 'static>(reader: R, writer: W, init: Duration, turn: Duration) -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() { return }
+            }
+        });
+        Timed { lines, writer, timeout: init, turn }
+    }
+}
+
+impl<W: Write> Transport for Timed<W> {
+    fn receive(&mut self) -> Option<String> {
+        let line = self.lines.recv_timeout(self.timeout).ok();
+        self.timeout = self.turn;
+        line
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), failure::Error> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// In-memory transport: lines sent on one end of a pair arrive at the other.
+pub struct Channel {
+    sender: mpsc::Sender<String>,
+    receiver: mpsc::Receiver<String>,
+}
+
+impl Channel {
+    pub fn pair() -> (Channel, Channel) {
+        let (a_sender, b_receiver) = mpsc::channel();
+        let (b_sender, a_receiver) = mpsc::channel();
+        let a = Channel { sender: a_sender, receiver: a_receiver };
+        let b = Channel { sender: b_sender, receiver: b_receiver };
+        (a, b)
+    }
+}
+
+impl Transport for Channel {
+    fn receive(&mut self) -> Option<String> {
+        self.receiver.recv().ok()
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), failure::Error> {
+        self.sender.send(line.to_string())
+            .map_err(|_| failure::err_msg("channel closed"))
+    }
+}
@@ -0,0 +1,204 @@
+extern crate my_bot;
+
+use std::thread;
+use std::time::Duration;
+
+use my_bot::{Bot, Channel, Command, Constants, Dir, Executor, Params, Profile, Remote, Ship, Shipyard, State};
+
+fn executor(state: &State) -> Executor {
+    let total = state.halite.iter().sum();
+    let profile = Profile::new(state.scores.len(), state.width, state.height, Params::default());
+    Executor::with_budget(total, profile, Duration::from_secs(60))
+}
+
+fn idle(_: &Constants, _: &State) -> Vec<Command> {
+    Vec::new()
+}
+
+#[test]
+fn surrounded_ship_at_yard_does_not_deadlock() {
+    let constants = Constants { MAX_TURNS: 30, ..Constants::default() };
+    let yards = vec![
+        Shipyard { owner: 0, x: 8, y: 8 },
+        Shipyard { owner: 1, x: 0, y: 0 },
+    ];
+    let mut state = my_bot::setup(&constants, 16, 16, yards, vec![100; 16 * 16]);
+    let around = [(8, 8, 0), (8, 7, 900), (9, 8, 900), (8, 9, 900), (7, 8, 900)];
+    state.ships = around.iter()
+        .enumerate()
+        .map(|(id, (x, y, halite))| Ship { owner: 0, id, x: *x, y: *y, halite: *halite })
+        .collect();
+
+    let mut ours = executor(&state);
+    let mut theirs = idle;
+    let history = my_bot::play(&constants, state, &mut [&mut ours, &mut theirs]);
+
+    // Every loaded ship gets a turn on the yard
+    for id in 1..around.len() {
+        assert!(history.states.iter().any(|state| {
+            state.ships.iter().any(|ship| ship.id == id && (ship.x, ship.y) == (8, 8))
+        }));
+    }
+}
+
+#[test]
+fn self_play_is_deterministic() {
+    let constants = Constants { GAME_SEED: 7, MAX_TURNS: 100, ..Constants::default() };
+    let (yards, halite) = my_bot::generate(&constants, 2, 32, 32).unwrap();
+    let state = my_bot::setup(&constants, 32, 32, yards, halite);
+
+    let run = || {
+        let (mut a, mut b) = (executor(&state), executor(&state));
+        my_bot::play(&constants, state.clone(), &mut [&mut a, &mut b])
+    };
+
+    let (first, second) = (run(), run());
+    assert_eq!(first.commands, second.commands);
+    assert_eq!(first.last().ships, second.last().ships);
+    assert_eq!(first.last().scores, second.last().scores);
+}
+
+#[test]
+fn bot_plays_the_same_over_a_transport() {
+    let constants = Constants { GAME_SEED: 7, MAX_TURNS: 100, ..Constants::default() };
+    let (yards, halite) = my_bot::generate(&constants, 2, 32, 32).unwrap();
+    let state = my_bot::setup(&constants, 32, 32, yards, halite);
+
+    let (engine, bot) = Channel::pair();
+    let params = Params { budget: 60000, ..Params::default() };
+    let bot = thread::spawn(move || Bot::new(bot, params).and_then(Bot::play));
+
+    let mut remote = Remote::new(engine);
+    let mut theirs = executor(&state);
+    let remote_history = my_bot::play(&constants, state.clone(), &mut [&mut remote, &mut theirs]);
+    assert_eq!(remote.name(), Some("nwtnni-0"));
+    assert_eq!(remote.stopped(), None);
+
+    // Hanging up ends the bot's game
+    drop(remote);
+    bot.join().unwrap().unwrap();
+
+    let (mut a, mut b) = (executor(&state), executor(&state));
+    let local_history = my_bot::play(&constants, state, &mut [&mut a, &mut b]);
+    assert_eq!(remote_history.commands, local_history.commands);
+    assert_eq!(remote_history.last().scores, local_history.last().scores);
+}
+
+#[test]
+fn returning_ships_wait_outside_a_crowded_deposit() {
+    let constants = Constants::default();
+    let yards = vec![
+        Shipyard { owner: 0, x: 8, y: 8 },
+        Shipyard { owner: 1, x: 24, y: 24 },
+    ];
+    let mut state = my_bot::setup(&constants, 32, 32, yards, vec![10; 32 * 32]);
+    state.round = 1;
+
+    // Full ship 0 is four steps out, behind nine full ships around the yard
+    let crowd = [(8, 12), (8, 7), (9, 8), (8, 9), (7, 8), (9, 9), (7, 7), (9, 7), (7, 9), (8, 6)];
+    state.ships = crowd.iter()
+        .enumerate()
+        .map(|(id, (x, y))| Ship { owner: 0, id, x: *x, y: *y, halite: 1000 })
+        .collect();
+
+    let moves = |state: &State| {
+        executor(state).execute(&constants, state)
+            .into_iter()
+            .any(|command| match command {
+            | Command::Move(0, dir) => dir != Dir::O,
+            | _ => false,
+            })
+    };
+
+    assert!(!moves(&state));
+    state.ships.truncate(1);
+    assert!(moves(&state));
+}
+
+#[test]
+fn ships_keep_depositing_without_time_to_plan() {
+    let constants = Constants { GAME_SEED: 1, MAX_TURNS: 400, ..Constants::default() };
+    let (yards, halite) = my_bot::generate(&constants, 2, 32, 32).unwrap();
+    let state = my_bot::setup(&constants, 32, 32, yards, halite);
+
+    // Every turn runs out of time at once, so only the fallbacks plan
+    let rushed = || {
+        let total = state.halite.iter().sum();
+        let profile = Profile::new(2, 32, 32, Params::default());
+        Executor::with_budget(total, profile, Duration::ZERO)
+    };
+
+    let (mut a, mut b) = (rushed(), rushed());
+    let history = my_bot::play(&constants, state.clone(), &mut [&mut a, &mut b]);
+    for score in &history.last().scores {
+        assert!(*score > constants.INITIAL_ENERGY, "final scores {:?}", history.last().scores);
+    }
+}
+
+#[test]
+fn idle_ships_clear_the_yard_for_returning_ones() {
+    let constants = Constants { MAX_TURNS: 30, ..Constants::default() };
+    let yards = vec![
+        Shipyard { owner: 0, x: 8, y: 8 },
+        Shipyard { owner: 1, x: 0, y: 0 },
+    ];
+
+    // Nothing left to mine, so neither ship has a target once it's empty
+    let mut state = my_bot::setup(&constants, 16, 16, yards, vec![0; 16 * 16]);
+    state.ships = vec![
+        Ship { owner: 0, id: 0, x: 9, y: 8, halite: 1000 },
+        Ship { owner: 0, id: 1, x: 7, y: 8, halite: 1000 },
+    ];
+
+    let mut ours = executor(&state);
+    let mut theirs = idle;
+    let history = my_bot::play(&constants, state, &mut [&mut ours, &mut theirs]);
+
+    // Both deposit long before the end-game crash could let them in
+    let deposited = history.states.iter()
+        .position(|state| state.scores[0] == constants.INITIAL_ENERGY + 2000);
+    assert!(deposited.is_some_and(|turn| turn < 10), "deposited on turn {:?}", deposited);
+}
+
+#[test]
+fn recalled_ships_deposit_everything_before_the_end() {
+    let constants = Constants { MAX_TURNS: 24, ..Constants::default() };
+    let yards = vec![
+        Shipyard { owner: 0, x: 16, y: 16 },
+        Shipyard { owner: 1, x: 0, y: 0 },
+    ];
+
+    // Loaded ships from two to twelve steps out, with enough halite around
+    // that they'd rather keep mining, and the yard's surroundings mined out
+    // as they would be by the end of a game
+    let mut halite = vec![200; 32 * 32];
+    for (index, cell) in halite.iter_mut().enumerate() {
+        let (x, y) = (index % 32, index / 32);
+        if x.abs_diff(16) + y.abs_diff(16) <= 4 || index == 0 { *cell = 0; }
+    }
+    let mut state = my_bot::setup(&constants, 32, 32, yards, halite);
+    let ships = [(16, 14), (20, 16), (16, 22), (8, 16), (16, 6), (28, 16), (22, 22), (10, 10), (16, 4)];
+    state.ships = ships.iter()
+        .enumerate()
+        .map(|(id, (x, y))| Ship { owner: 0, id, x: *x, y: *y, halite: 300 })
+        .collect();
+
+    let mut ours = executor(&state);
+    let mut theirs = idle;
+    let history = my_bot::play(&constants, state, &mut [&mut ours, &mut theirs]);
+
+    // Nothing is spilled on the way in, and every ship either deposits or is
+    // destroyed on the yard, so all of its cargo reached our score
+    for (turn, states) in history.states.windows(2).enumerate() {
+        let spilled = states[0].halite.iter().zip(&states[1].halite).any(|(before, after)| after > before);
+        assert!(!spilled, "cargo spilled on turn {}", turn + 1);
+    }
+    for id in 0..ships.len() {
+        let home = history.states.iter().skip(1).any(|state| {
+            state.ships.iter()
+                .find(|ship| ship.id == id)
+                .is_none_or(|ship| (ship.x, ship.y) == (16, 16))
+        });
+        assert!(home, "ship {} never got home", id);
+    }
+}